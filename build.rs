@@ -0,0 +1,64 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Collect the files under the directory, as paths relative to it.
+fn collect_files(base_dir: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(base_dir, &path, files)?;
+        } else {
+            files.push(path.strip_prefix(base_dir).unwrap().to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+// Generate a table of (relative path, contents) for the files in the directory.
+fn generate_table(name: &str, dir: &Path) -> io::Result<String> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let mut table = format!("pub static {}: &[(&str, &str)] = &[\n", name);
+    for file in files {
+        let key = file
+            .iter()
+            .map(|piece| piece.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        table.push_str(&format!(
+            "    ({:?}, include_str!({:?})),\n",
+            key,
+            dir.join(&file).display().to_string()
+        ));
+    }
+    table.push_str("];\n");
+
+    Ok(table)
+}
+
+fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // The data is only compiled in with the "embedded-data" feature.
+    if env::var_os("CARGO_FEATURE_EMBEDDED_DATA").is_none() {
+        return Ok(());
+    }
+
+    println!("cargo:rerun-if-changed=boilerplate");
+    println!("cargo:rerun-if-changed=spec-data");
+
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("embedded_data.rs");
+
+    let mut code = generate_table("BOILERPLATE", &manifest_dir.join("boilerplate"))?;
+    code.push_str(&generate_table(
+        "SPEC_DATA",
+        &manifest_dir.join("spec-data"),
+    )?);
+
+    fs::write(out_path, code)
+}
@@ -3,31 +3,50 @@ use kuchiki::traits::*;
 use kuchiki::{NodeData, NodeRef};
 use markup5ever::LocalName;
 use std::char;
-use std::fs;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use crate::config;
 use crate::config::DFN_SELECTOR;
+use crate::datadir::DataDirs;
 use crate::html::{self, Attr};
+use crate::link::biblio::Biblio;
 use crate::link::reference::Reference;
 use crate::metadata::parse::Editor;
 use crate::spec::Spec;
+use crate::util::date::Date;
 
 // Retrieve boilerplate file with doc (metadata).
-fn retrieve_boilerplate(doc: &Spec, name: &str) -> String {
-    if doc.md.boilerplate.get(name) {
-        retrieve_boilerplate_with_info(name, doc.md.group.as_deref(), doc.md.raw_status.as_deref())
-    } else {
-        String::new()
+fn retrieve_boilerplate(doc: &mut Spec, name: &str) -> String {
+    if !doc.md.boilerplate.get(name) {
+        return String::new();
+    }
+
+    match retrieve_boilerplate_with_info(
+        &doc.data_dirs,
+        name,
+        doc.md.group.as_deref(),
+        doc.md.raw_status.as_deref(),
+    ) {
+        Ok((data, path)) => {
+            doc.boilerplate_files.extend(path);
+            data
+        }
+        Err(message) => {
+            doc.diagnostics.error(message, None);
+            String::new()
+        }
     }
 }
 
-// Retrieve boilerplate file with group and status.
+// Retrieve boilerplate file with group and status, along with its path if it
+// isn't embedded.
 pub fn retrieve_boilerplate_with_info(
+    data_dirs: &DataDirs,
     name: &str,
     group: Option<&str>,
     status: Option<&str>,
-) -> String {
+) -> Result<(String, Option<PathBuf>), String> {
     // File Priorities:
     // 1. [status file with group]
     // 2. [generic file with group]
@@ -37,6 +56,8 @@ pub fn retrieve_boilerplate_with_info(
     let mut paths_to_try = Vec::new();
 
     let status_filename = if let Some(status) = status {
+        // Status files are named after the status without its megagroup.
+        let status = status.rsplit('/').next().unwrap_or(status);
         Some(format!("{}-{}.include", name, status))
     } else {
         None
@@ -45,7 +66,7 @@ pub fn retrieve_boilerplate_with_info(
     if let Some(ref status_filename) = status_filename {
         // status file with group
         if let Some(group) = group {
-            paths_to_try.push(Path::new("boilerplate").join(group).join(status_filename));
+            paths_to_try.push(Path::new(group).join(status_filename));
         }
     }
 
@@ -53,24 +74,20 @@ pub fn retrieve_boilerplate_with_info(
 
     if let Some(group) = group {
         // generic file with group
-        paths_to_try.push(Path::new("boilerplate").join(group).join(&generic_filename));
+        paths_to_try.push(Path::new(group).join(&generic_filename));
     }
 
     if let Some(ref status_filename) = status_filename {
         // status file without group
-        paths_to_try.push(Path::new("boilerplate").join(status_filename));
+        paths_to_try.push(PathBuf::from(status_filename));
     }
 
     // generic file without group
-    paths_to_try.push(Path::new("boilerplate").join(&generic_filename));
+    paths_to_try.push(PathBuf::from(&generic_filename));
 
-    for path in paths_to_try {
-        if let Ok(data) = fs::read_to_string(path) {
-            return data;
-        }
-    }
-
-    die!("Can't find an appropriate include file for {}.", name);
+    data_dirs
+        .read_boilerplate(&paths_to_try)
+        .ok_or_else(|| format!("Can't find an appropriate include file for {}.", name))
 }
 
 pub fn load_containers(doc: &mut Spec) {
@@ -233,7 +250,7 @@ pub fn fill_spec_metadata_section(doc: &mut Spec) {
 
     fn key_to_dt_node(key: &str) -> NodeRef {
         let dt_el = match key {
-            "Editor" => html::new_element(
+            "Editor" | "Former Editor" => html::new_element(
                 "dt",
                 btreemap! {
                     "class" => "editor"
@@ -251,50 +268,209 @@ pub fn fill_spec_metadata_section(doc: &mut Spec) {
         dd_el
     }
 
+    fn link_to_dd_node(url: &str) -> NodeRef {
+        wrap_in_dd_node(html::new_a(
+            btreemap! {
+                "href" => url
+            },
+            url,
+        ))
+    }
+
     let macros = &doc.macros;
 
-    // <dt> and <dd> nodes that would be appended to <dl> node
-    let mut md_list = Vec::new();
+    // key => <dd> nodes of built-in metadata
+    let mut rows: IndexMap<&str, Vec<NodeRef>> = IndexMap::new();
 
     // Insert version.
     if let Some(version) = macros.get("version") {
-        md_list.push(key_to_dt_node("This version"));
-        md_list.push(wrap_in_dd_node(html::new_a(
-            btreemap! {
-                "class" => "u-url",
-                "href" => version,
-            },
-            version,
-        )));
+        rows.insert(
+            "This version",
+            vec![wrap_in_dd_node(html::new_a(
+                btreemap! {
+                    "class" => "u-url",
+                    "href" => version,
+                },
+                version,
+            ))],
+        );
     }
 
     // Insert latest published version.
     if let Some(ref tr) = doc.md.tr {
-        md_list.push(key_to_dt_node("Latest published version"));
-        md_list.push(wrap_in_dd_node(html::new_a(
-            btreemap! {
-                "href" => tr
-            },
-            tr,
-        )));
+        rows.insert("Latest published version", vec![link_to_dd_node(tr)]);
+    }
+
+    // Insert previous versions.
+    if !doc.md.previous_versions.is_empty() {
+        rows.insert(
+            "Previous Version",
+            doc.md
+                .previous_versions
+                .iter()
+                .map(|url| {
+                    wrap_in_dd_node(html::new_a(
+                        btreemap! {
+                            "href" => url.to_owned(),
+                            "rel" => "prev".to_owned(),
+                        },
+                        url,
+                    ))
+                })
+                .collect(),
+        );
+    }
+
+    // Insert version history.
+    if !doc.md.version_history.is_empty() {
+        rows.insert(
+            "Version History",
+            doc.md
+                .version_history
+                .iter()
+                .map(|url| link_to_dd_node(url))
+                .collect(),
+        );
+    }
+
+    // Insert implementation report.
+    if let Some(ref url) = doc.md.implementation_report {
+        rows.insert("Implementation Report", vec![link_to_dd_node(url)]);
+    }
+
+    // Insert test suite.
+    if let Some(ref url) = doc.md.test_suite {
+        rows.insert("Test Suite", vec![link_to_dd_node(url)]);
+    }
+
+    // Insert mailing list.
+    if let Some(ref email) = doc.md.mailing_list {
+        rows.insert(
+            "Mailing List",
+            vec![wrap_in_dd_node(html::new_a(
+                btreemap! {
+                    "href" => format!("mailto:{}", email)
+                },
+                email,
+            ))],
+        );
+    }
+
+    // Insert repository.
+    if let Some(ref url) = doc.md.repository {
+        rows.insert("Repository", vec![link_to_dd_node(url)]);
+    }
+
+    // Insert issue trackers.
+    if !doc.md.issue_tracking.is_empty() {
+        rows.insert(
+            "Issue Tracking",
+            doc.md
+                .issue_tracking
+                .iter()
+                .map(|(label, url)| {
+                    wrap_in_dd_node(html::new_a(
+                        btreemap! {
+                            "href" => url
+                        },
+                        label,
+                    ))
+                })
+                .collect(),
+        );
     }
 
     // Insert editors.
     if !doc.md.editors.is_empty() {
-        md_list.push(key_to_dt_node("Editor"));
-        md_list.extend(doc.md.editors.iter().map(editor_to_dd_node));
+        rows.insert(
+            "Editor",
+            doc.md.editors.iter().map(editor_to_dd_node).collect(),
+        );
     }
 
-    // Insert custom metadata.
-    for (key, vals) in &doc.md.custom_md {
-        md_list.push(key_to_dt_node(key));
-        md_list.extend(vals.iter().map(|val| wrap_in_dd_node(html::new_text(val))));
+    // Insert former editors.
+    if !doc.md.former_editors.is_empty() {
+        rows.insert(
+            "Former Editor",
+            doc.md
+                .former_editors
+                .iter()
+                .map(editor_to_dd_node)
+                .collect(),
+        );
+    }
+
+    // Custom metadata values are HTML.
+    let custom_to_dd_nodes = |key: &str| -> Vec<NodeRef> {
+        let vals = match doc.md.custom_md.get(key) {
+            Some(vals) => vals,
+            None => return Vec::new(),
+        };
+
+        vals.iter()
+            .map(|val| {
+                let dd_el = html::new_element("dd", None::<Attr>);
+                let val_dom = kuchiki::parse_html().one(val.to_owned());
+                if let Some(body) = html::select_first(&val_dom, "body") {
+                    for child in body.children() {
+                        dd_el.append(child);
+                    }
+                }
+                dd_el
+            })
+            .collect()
+    };
+
+    // The order of the rows:
+    // "*" => the built-in metadata that isn't listed explicitly
+    // "!*" => the custom metadata that isn't listed explicitly
+    // A custom key with the same name as a built-in one joins its row.
+    let default_order = ["*".to_owned(), "!*".to_owned()];
+    let order = if doc.md.metadata_order.is_empty() {
+        &default_order[..]
+    } else {
+        &doc.md.metadata_order[..]
+    };
+
+    let mut keys = Vec::new();
+
+    for item in order {
+        match item.as_str() {
+            "*" => keys.extend(
+                rows.keys()
+                    .filter(|key| !order.iter().any(|item| item == *key))
+                    .map(|key| key.to_string()),
+            ),
+            "!*" => keys.extend(
+                doc.md
+                    .custom_md
+                    .keys()
+                    .filter(|key| !rows.contains_key(key.as_str()))
+                    .filter(|key| !order.contains(key))
+                    .cloned(),
+            ),
+            _ => keys.push(item.to_owned()),
+        }
     }
 
     let dl_el = html::new_element("dl", None::<Attr>);
 
-    for item in md_list {
-        dl_el.append(item);
+    for key in keys {
+        if !doc.md.metadata_include.get(&key) {
+            continue;
+        }
+
+        let mut dd_els = rows.get(key.as_str()).cloned().unwrap_or_default();
+        dd_els.extend(custom_to_dd_nodes(&key));
+
+        if dd_els.is_empty() {
+            continue;
+        }
+
+        dl_el.append(key_to_dt_node(&key));
+        for dd_el in dd_els {
+            dl_el.append(dd_el);
+        }
     }
 
     container.append(dl_el);
@@ -302,7 +478,7 @@ pub fn fill_spec_metadata_section(doc: &mut Spec) {
 
 pub fn fill_copyright_section(doc: &mut Spec) {
     let container = match get_container(doc, "copyright") {
-        Some(container) => container,
+        Some(container) => container.to_owned(),
         None => return,
     };
 
@@ -319,12 +495,16 @@ pub fn fill_copyright_section(doc: &mut Spec) {
 
 pub fn fill_abstract_section(doc: &mut Spec) {
     let container = match get_container(doc, "abstract") {
-        Some(container) => container,
+        Some(container) => container.to_owned(),
         None => return,
     };
 
-    let mut abs = retrieve_boilerplate(doc, "abstract");
-    abs = doc.fix_text(&abs);
+    // The abstract macro is fixed already, so only the text around it is.
+    let pieces = retrieve_boilerplate(doc, "abstract")
+        .split("[ABSTRACT]")
+        .map(|piece| doc.fix_text(piece))
+        .collect::<Vec<String>>();
+    let abs = pieces.join(doc.macros.get("abstract").map_or("", String::as_str));
     let abs_dom = kuchiki::parse_html().one(abs);
 
     if let Some(body) = html::select_first(&abs_dom, "body") {
@@ -334,10 +514,61 @@ pub fn fill_abstract_section(doc: &mut Spec) {
     }
 }
 
+// Append a boilerplate file (with macros filled) to the container.
+fn append_boilerplate(doc: &mut Spec, container: &NodeRef, name: &str) {
+    let mut data = retrieve_boilerplate(doc, name);
+    data = doc.fix_text(&data);
+    let dom = kuchiki::parse_html().one(data);
+
+    if let Some(body) = html::select_first(&dom, "body") {
+        for child in body.children() {
+            container.append(child);
+        }
+    }
+}
+
+pub fn add_warning(doc: &mut Spec) {
+    let name = match doc.md.warning {
+        Some(ref warning) => warning.boilerplate_name(),
+        None => return,
+    };
+
+    let container = match get_container(doc, "warning") {
+        Some(container) => container.to_owned(),
+        None => return,
+    };
+
+    append_boilerplate(doc, &container, name);
+}
+
+pub fn add_expiry_notice(doc: &mut Spec) {
+    let expires = match doc.md.expiry_date() {
+        Some(expires) => expires.to_owned(),
+        None => return,
+    };
+
+    let name = if expires <= doc.md.date || expires <= Date::now() {
+        "warning-expired"
+    } else {
+        doc.extra_scripts
+            .insert("expires", include_str!("script/expires.js"));
+        "warning-expires"
+    };
+
+    html::add_class(doc.body(), name);
+
+    let container = match get_container(doc, "warning") {
+        Some(container) => container.to_owned(),
+        None => return,
+    };
+
+    append_boilerplate(doc, &container, name);
+}
+
 pub fn add_index_section(doc: &mut Spec) {
     let mut dfn_els = html::select(doc.dom(), &DFN_SELECTOR);
 
-    if dfn_els.next().is_none() {
+    if dfn_els.next().is_none() && doc.external_references_used.is_empty() {
         return;
     }
 
@@ -376,7 +607,16 @@ fn index_items_to_node(index_entries: &mut IndexMap<String, Vec<IndexTerm>>) ->
         },
     );
 
-    index_entries.sort_keys();
+    // Sort the terms as if they were written in lowercase letters and digits only.
+    let sort_key = |link_text: &str| {
+        link_text
+            .to_lowercase()
+            .chars()
+            .filter(|ch| ch.is_ascii_alphanumeric())
+            .collect::<String>()
+    };
+    index_entries
+        .sort_by(|link_text1, _, link_text2, _| sort_key(link_text1).cmp(&sort_key(link_text2)));
 
     for (link_text, index_items) in index_entries {
         if index_items.len() == 1 {
@@ -438,6 +678,10 @@ fn index_items_to_node(index_entries: &mut IndexMap<String, Vec<IndexTerm>>) ->
 }
 
 fn add_index_of_local_terms(doc: &Spec, container: &NodeRef) {
+    if html::select(doc.dom(), &DFN_SELECTOR).next().is_none() {
+        return;
+    }
+
     let h3_el = html::new_element(
         "h3",
         btreemap! {
@@ -452,8 +696,12 @@ fn add_index_of_local_terms(doc: &Spec, container: &NodeRef) {
     let mut index_entries: IndexMap<String, Vec<IndexTerm>> = IndexMap::new();
 
     for dfn_el in html::select(doc.dom(), &DFN_SELECTOR) {
+        // Dfns of unknown types are left without an id and aren't indexed.
+        let id = match html::get_attr(&dfn_el, "id") {
+            Some(id) => id,
+            None => continue,
+        };
         let link_text = html::get_text_content(&dfn_el);
-        let id = html::get_attr(&dfn_el, "id").unwrap();
         let heading_level = match html::get_relevant_heading_level(&dfn_el) {
             Some(heading_level) => heading_level,
             None => "Unnumbered section".to_owned(),
@@ -468,7 +716,7 @@ fn add_index_of_local_terms(doc: &Spec, container: &NodeRef) {
             ),
             None => match dfn_type.as_str() {
                 "dfn" => "definition of".to_owned(),
-                _ => dfn_type,
+                _ => format!("({})", dfn_type),
             },
         };
 
@@ -539,56 +787,66 @@ fn add_index_of_external_terms(doc: &mut Spec, container: &NodeRef) {
 
     let mut at_least_one_panel = false;
 
-    for (spec, references) in &doc.external_references_used {
-        for (link_text, reference) in references {
-            let name = reference.url.rsplitn(2, '#').next().unwrap();
-            let term_id = format!("term-for-{}", name);
-
-            let aside_el = make_external_panel(reference, name, &term_id);
-            container.append(aside_el);
+    let mut specs = doc
+        .external_references_used
+        .keys()
+        .cloned()
+        .collect::<Vec<String>>();
+    specs.sort_by_key(|spec| spec.to_uppercase());
+
+    for spec in specs {
+        // Specs are named as in their bibliography entries where possible.
+        let spec_name = match doc.biblio_manager.get_biblio(&spec) {
+            Ok(Some(biblio)) => biblio.link_text,
+            _ => spec.to_owned(),
+        };
 
-            let spec_li_el = {
-                let spec_li_el = html::new_element("li", None::<Attr>);
+        let spec_li_el = html::new_element("li", None::<Attr>);
 
-                let a_el = html::new_a(
-                    btreemap! {
-                        "data-link-type" => "biblio"
-                    },
-                    format!("[{}]", spec),
-                );
-                spec_li_el.append(a_el);
+        let a_el = html::new_a(
+            btreemap! {
+                "data-link-type" => "biblio"
+            },
+            format!("[{}]", spec_name),
+        );
+        spec_li_el.append(a_el);
 
-                spec_li_el.append(html::new_text(" defines the following terms:"));
+        spec_li_el.append(html::new_text(" defines the following terms:"));
 
-                let terms_ul_el = {
-                    let ul_el = html::new_element("ul", None::<Attr>);
+        let terms_ul_el = html::new_element("ul", None::<Attr>);
 
-                    let li_el = html::new_element("li", None::<Attr>);
+        let mut references = doc.external_references_used[&spec]
+            .iter()
+            .collect::<Vec<(&String, &Reference)>>();
+        references.sort_by_key(|(link_text, _)| link_text.to_owned());
 
-                    let span_el = html::new_element(
-                        "span",
-                        btreemap! {
-                            "class" => "dfn-paneled",
-                            "id" => &term_id,
-                            "style" => "color:initial",
-                        },
-                    );
-                    span_el.append(html::new_text(link_text));
-                    li_el.append(span_el);
+        for (link_text, reference) in references {
+            let name = reference.url.rsplit('#').next().unwrap();
+            let term_id = format!("term-for-{}", name);
 
-                    ul_el.append(li_el);
+            let aside_el = make_external_panel(reference, name, &term_id);
+            container.append(aside_el);
 
-                    ul_el
-                };
-                spec_li_el.append(terms_ul_el);
+            let li_el = html::new_element("li", None::<Attr>);
 
-                spec_li_el
-            };
+            let span_el = html::new_element(
+                "span",
+                btreemap! {
+                    "class" => "dfn-paneled",
+                    "id" => &term_id,
+                    "style" => "color:initial",
+                },
+            );
+            span_el.append(html::new_text(link_text));
+            li_el.append(span_el);
 
-            ul_el.append(spec_li_el);
+            terms_ul_el.append(li_el);
 
             at_least_one_panel = true;
         }
+
+        spec_li_el.append(terms_ul_el);
+        ul_el.append(spec_li_el);
     }
 
     if at_least_one_panel {
@@ -612,6 +870,12 @@ fn add_index_of_external_terms(doc: &mut Spec, container: &NodeRef) {
 }
 
 pub fn add_references_section(doc: &mut Spec) {
+    fn sorted_biblios(biblios: &HashMap<String, Biblio>) -> Vec<&Biblio> {
+        let mut biblios = biblios.values().collect::<Vec<&Biblio>>();
+        biblios.sort_by_key(|biblio| biblio.link_text.to_lowercase());
+        biblios
+    }
+
     fn format_biblio_term(link_text: &str) -> String {
         if link_text
             .chars()
@@ -655,7 +919,7 @@ pub fn add_references_section(doc: &mut Spec) {
 
         let dl_el = html::new_element("dl", None::<Attr>);
 
-        for normative_biblio in doc.normative_biblios.values() {
+        for normative_biblio in sorted_biblios(&doc.normative_biblios) {
             let id = format!(
                 "biblio-{}",
                 config::generate_name(&normative_biblio.link_text)
@@ -692,7 +956,7 @@ pub fn add_references_section(doc: &mut Spec) {
 
         let dl_el = html::new_element("dl", None::<Attr>);
 
-        for informative_biblio in doc.informative_biblios.values() {
+        for informative_biblio in sorted_biblios(&doc.informative_biblios) {
             let id = format!(
                 "biblio-{}",
                 config::generate_name(&informative_biblio.link_text)
@@ -719,7 +983,7 @@ pub fn add_references_section(doc: &mut Spec) {
 
 pub fn fill_toc_section(doc: &mut Spec) {
     let container = match get_container(doc, "table-of-contents") {
-        Some(container) => container,
+        Some(container) => container.to_owned(),
         None => return,
     };
 
@@ -751,26 +1015,34 @@ pub fn fill_toc_section(doc: &mut Spec) {
 
     let mut previous_level = 1;
 
-    for heading_el in html::select(doc.dom(), "h2, h3, h4, h5, h6") {
+    let heading_els = html::select(doc.dom(), "h2, h3, h4, h5, h6").collect::<Vec<NodeRef>>();
+
+    for heading_el in heading_els {
         let heading_tag = html::get_tag(&heading_el).unwrap();
         let curr_level = heading_tag.chars().last().unwrap().to_digit(10).unwrap() as usize;
 
         if curr_level > previous_level + 1 {
-            die!(
-                "Heading level jumps more than one level, from h{} to h{}",
-                previous_level,
-                curr_level
-            )
+            doc.diagnostics.error(
+                format!(
+                    "Heading level jumps more than one level, from h{} to h{}",
+                    previous_level, curr_level
+                ),
+                None,
+            );
         }
 
         let curr_ol_el = if let Some(ref curr_ol_el) = ol_cells[curr_level - 2] {
-            curr_ol_el
+            curr_ol_el.to_owned()
         } else {
-            die!(
-                "Saw an <h{}> without seeing an <h{}> first. Please order your headings properly.",
-                curr_level,
-                curr_level - 1
-            )
+            doc.diagnostics.error(
+                format!(
+                    "Saw an <h{}> without seeing an <h{}> first. Please order your headings properly.",
+                    curr_level,
+                    curr_level - 1
+                ),
+                None,
+            );
+            continue;
         };
 
         if html::has_class(&heading_el, "no-toc") {
@@ -826,7 +1098,7 @@ pub fn fill_toc_section(doc: &mut Spec) {
 
     // Remove empty <ol> nodes.
     loop {
-        let ol_els = html::select(container, "ol:empty").collect::<Vec<NodeRef>>();
+        let ol_els = html::select(&container, "ol:empty").collect::<Vec<NodeRef>>();
 
         if ol_els.is_empty() {
             break;
@@ -75,6 +75,16 @@ pub fn clean_dom(doc: &Spec) {
         }
     }
 
+    // Containers only pass their dfn type and for values down to their dfns.
+    let dfn_els = html::select(doc.dom(), &DFN_SELECTOR).collect::<Vec<NodeRef>>();
+
+    for el in html::select(doc.dom(), "[data-dfn-type], [data-dfn-for]") {
+        if !dfn_els.contains(&el) {
+            html::remove_attr(&el, "data-dfn-type");
+            html::remove_attr(&el, "data-dfn-for");
+        }
+    }
+
     for a_el in html::select(doc.dom(), "a") {
         // TODO: Remove attributes elsewhere.
         html::remove_attr(&a_el, "data-link-for");
@@ -1,29 +1,554 @@
+use chrono::Local;
 use clap::{App, Arg, ArgMatches, SubCommand};
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::net::TcpListener;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::metadata::Metadata;
-use crate::spec::Spec;
+use crate::server::{self, Preview};
+use bikeshed_rs::{
+    update_spec_data, DataDirs, Metadata, MetadataSource, Severity, Spec, SpecDataCache,
+    SOURCE_FILE_EXTENSIONS,
+};
 
-fn handle_spec(matches: ArgMatches) {
-    let infile = matches
-        .subcommand_matches("spec")
-        .unwrap()
-        .value_of("infile")
-        .unwrap();
-    let outfile = matches
-        .subcommand_matches("spec")
-        .unwrap()
-        .value_of("outfile");
+// how often the watched files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+// Separate the "--md-<Key>=<value>" flags from the other arguments. Values of
+// flags given as a separate argument and everything after "--" are left alone,
+// even if they start with "--md-".
+fn split_md_args(args: impl IntoIterator<Item = String>) -> (Vec<String>, Vec<String>) {
+    const VALUE_FLAGS: [&str; 5] = [
+        "--boilerplate-dir",
+        "--from",
+        "--md-file",
+        "--port",
+        "--spec-data-dir",
+    ];
+
+    let mut md_args = Vec::new();
+    let mut other_args = Vec::new();
+
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            other_args.push(arg);
+            other_args.extend(args);
+            break;
+        }
+
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            other_args.push(arg);
+            other_args.extend(args.next());
+        } else if arg.starts_with("--md-") && !arg.starts_with("--md-file=") {
+            md_args.push(arg);
+        } else {
+            other_args.push(arg);
+        }
+    }
+
+    (md_args, other_args)
+}
 
-    let md_cli = Metadata::new();
+// Parse "--md-<Key>=<value>" flags into command-line metadata. Dashes in the
+// key stand for spaces, so "--md-work-status=stable" sets "Work Status".
+fn parse_md_args(md_args: &[String]) -> (Metadata, Vec<String>) {
+    let mut md = Metadata::with_source(MetadataSource::CommandLine);
+    let mut errors = Vec::new();
+
+    for arg in md_args {
+        let flag = &arg["--md-".len()..];
+        let (key, val) = match flag.find('=') {
+            Some(pos) => (&flag[..pos], &flag[pos + 1..]),
+            None => {
+                errors.push(format!(
+                    "The \"{}\" flag needs a value, as in \"{}=<value>\".",
+                    arg, arg
+                ));
+                continue;
+            }
+        };
+
+        if let Err(message) = md.add_data(&key.replace('-', " "), val) {
+            errors.push(message);
+        }
+    }
+
+    (md, errors)
+}
+
+// Command-line settings shared by the subcommands that build a spec.
+struct BuildOptions<'a> {
+    md_args: &'a [String],
+    md_file: Option<&'a str>,
+    boilerplate_dir: Option<&'a str>,
+    spec_data_dir: Option<&'a str>,
+}
+
+impl<'a> BuildOptions<'a> {
+    fn new(matches: &'a ArgMatches, md_args: &'a [String]) -> Self {
+        BuildOptions {
+            md_args,
+            md_file: matches.value_of("md-file"),
+            boilerplate_dir: matches.value_of("boilerplate-dir"),
+            spec_data_dir: matches.value_of("spec-data-dir"),
+        }
+    }
+}
+
+// Load a spec and process it with the build function.
+fn build_spec<'a>(
+    infile: &'a str,
+    options: &BuildOptions,
+    build: impl FnOnce(&mut Spec<'a>),
+) -> Spec<'a> {
+    let (md_cli, md_errors) = parse_md_args(options.md_args);
 
     let mut doc = Spec::new(infile, md_cli);
-    doc.preprocess();
-    doc.finish(outfile);
+    doc.data_dirs = DataDirs::resolve(infile, options.boilerplate_dir, options.spec_data_dir);
+    doc.md_file = options.md_file.map(PathBuf::from);
+    for message in md_errors {
+        doc.diagnostics.error(message, None);
+    }
+    build(&mut doc);
+
+    doc
+}
+
+fn print_diagnostics(doc: &Spec) {
+    for diagnostic in doc.diagnostics.iter() {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+fn is_source_file(path: &str) -> bool {
+    SOURCE_FILE_EXTENSIONS
+        .iter()
+        .any(|extension| path.ends_with(extension))
+}
+
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// Split the paths given to "spec" into the inputs and the output file. Only a
+// single source file can be followed by an output file ("spec foo.bs
+// out.html"), all other paths are inputs that get rendered next to themselves.
+fn split_spec_paths<'a>(paths: &[&'a str]) -> (Vec<&'a str>, Option<&'a str>) {
+    let is_batch_path = |path: &str| is_glob(path) || Path::new(path).is_dir();
+
+    match paths {
+        [infile, outfile] if !is_batch_path(infile) && !is_batch_path(outfile) => {
+            if is_source_file(outfile) {
+                (paths.to_vec(), None)
+            } else {
+                (vec![infile], Some(outfile))
+            }
+        }
+        _ => (paths.to_vec(), None),
+    }
+}
+
+// Expand directories and glob patterns into the source files they contain.
+fn expand_inputs(paths: &[&str]) -> Result<Vec<String>, String> {
+    let mut infiles = Vec::new();
+
+    for path in paths {
+        if Path::new(path).is_dir() {
+            let entries = fs::read_dir(path)
+                .map_err(|err| format!("Unable to read \"{}\": {}.", path, err))?;
+            let mut dir_infiles = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|entry| entry.is_file())
+                .map(|entry| entry.to_string_lossy().into_owned())
+                .filter(|entry| is_source_file(entry))
+                .collect::<Vec<String>>();
+            dir_infiles.sort();
+            infiles.extend(dir_infiles);
+        } else if is_glob(path) {
+            let entries = glob::glob(path)
+                .map_err(|err| format!("Invalid pattern \"{}\": {}.", path, err))?;
+            infiles.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.is_file())
+                    .map(|entry| entry.to_string_lossy().into_owned()),
+            );
+        } else {
+            infiles.push(path.to_string());
+        }
+    }
+
+    if infiles.is_empty() {
+        return Err("No spec source files found.".to_owned());
+    }
+
+    if infiles.len() > 1 && infiles.iter().any(|infile| infile == "-") {
+        return Err("Stdin (\"-\") can't be rendered along with other specs.".to_owned());
+    }
+
+    Ok(infiles)
+}
+
+// The outcome of rendering one spec of a batch.
+struct BatchResult {
+    index: usize,
+    worst: Option<Severity>,
+    messages: Vec<String>,
+    elapsed: Duration,
+}
+
+// Render the specs in parallel, sharing the loaded spec data between them.
+// Diagnostics are printed as each spec finishes, followed by a summary.
+fn render_batch(
+    infiles: &[String],
+    options: &BuildOptions,
+    spec_data_cache: &SpecDataCache,
+) -> Option<Severity> {
+    let next_index = AtomicUsize::new(0);
+    let workers = thread::available_parallelism()
+        .map(|workers| workers.get())
+        .unwrap_or(1)
+        .min(infiles.len());
+    let (sender, receiver) = mpsc::channel();
+
+    let mut results = thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next_index = &next_index;
+
+            scope.spawn(move || loop {
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let infile = match infiles.get(index) {
+                    Some(infile) => infile,
+                    None => break,
+                };
+
+                let start = Instant::now();
+                // A crash on one spec shouldn't take the others down with it.
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let doc = build_spec(infile, options, |doc| {
+                        doc.spec_data_cache = spec_data_cache.clone();
+                        doc.preprocess();
+                        doc.finish(None);
+                    });
+
+                    let messages = doc
+                        .diagnostics
+                        .iter()
+                        .map(|diagnostic| match diagnostic.file {
+                            Some(_) => diagnostic.to_string(),
+                            None => format!("{}: {}", infile, diagnostic),
+                        })
+                        .collect();
+
+                    (doc.diagnostics.worst(), messages)
+                }));
+                let (worst, messages) = outcome.unwrap_or_else(|_| {
+                    let message = format!("{}: {}: Processing crashed.", infile, Severity::Fatal);
+                    (Some(Severity::Fatal), vec![message])
+                });
+
+                let result = BatchResult {
+                    index,
+                    worst,
+                    messages,
+                    elapsed: start.elapsed(),
+                };
+                if sender.send(result).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        receiver
+            .iter()
+            .inspect(|result| {
+                for message in result.messages.iter() {
+                    eprintln!("{}", message);
+                }
+            })
+            .collect::<Vec<BatchResult>>()
+    });
+    results.sort_by_key(|result| result.index);
+
+    println!("Summary:");
+    for result in results.iter() {
+        let outcome = match result.worst {
+            Some(severity) => severity.to_string(),
+            None => "ok".to_owned(),
+        };
+        println!(
+            "  {:<11} {} ({} ms)",
+            outcome,
+            infiles[result.index],
+            result.elapsed.as_millis()
+        );
+    }
+
+    let count = |filter: fn(Option<Severity>) -> bool| {
+        results.iter().filter(|result| filter(result.worst)).count()
+    };
+    println!(
+        "Rendered {} specs: {} ok, {} with warnings, {} with errors.",
+        results.len(),
+        count(|worst| worst.is_none()),
+        count(|worst| worst == Some(Severity::Warning)),
+        count(|worst| worst > Some(Severity::Warning)),
+    );
+
+    results.iter().filter_map(|result| result.worst).max()
+}
+
+fn handle_spec(matches: ArgMatches, md_args: &[String]) {
+    let spec_matches = matches.subcommand_matches("spec").unwrap();
+    let paths = spec_matches
+        .values_of("infile")
+        .unwrap()
+        .collect::<Vec<&str>>();
+    let options = BuildOptions::new(spec_matches, md_args);
+
+    let (inputs, outfile) = split_spec_paths(&paths);
+    let infiles = match expand_inputs(&inputs) {
+        Ok(infiles) => infiles,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(Severity::Fatal.exit_code());
+        }
+    };
+
+    let worst = if spec_matches.is_present("print-metadata") {
+        if infiles.len() != 1 || outfile.is_some() {
+            eprintln!("\"--print-metadata\" takes a single source file.");
+            process::exit(Severity::Fatal.exit_code());
+        }
+        let doc = build_spec(&infiles[0], &options, Spec::preprocess);
+        print_diagnostics(&doc);
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&doc.md.to_json()).unwrap()
+        );
+        doc.diagnostics.worst()
+    } else if infiles.len() == 1 && inputs == [infiles[0].as_str()] {
+        let doc = build_spec(&infiles[0], &options, |doc| {
+            doc.preprocess();
+            doc.finish(outfile);
+        });
+        print_diagnostics(&doc);
+        doc.diagnostics.worst()
+    } else {
+        render_batch(&infiles, &options, &SpecDataCache::default())
+    };
+
+    if let Some(severity) = worst {
+        process::exit(severity.exit_code());
+    }
+}
+
+fn modified_times(paths: &BTreeSet<PathBuf>) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|md| md.modified()).ok())
+        .collect()
+}
+
+// Build a spec, then build it again whenever one of its files changes, until
+// the stop flag is set.
+fn rebuild_on_change<'a>(
+    infile: &'a str,
+    options: &BuildOptions,
+    stop: &AtomicBool,
+    mut build: impl FnMut(&mut Spec<'a>),
+) {
+    while !stop.load(Ordering::SeqCst) {
+        let start = Instant::now();
+        let started_at = SystemTime::now();
+        // A crash on one edit shouldn't stop the watching.
+        let doc = panic::catch_unwind(AssertUnwindSafe(|| build_spec(infile, options, &mut build)));
+        let elapsed = start.elapsed();
+
+        let (outcome, dependencies) = match doc {
+            Ok(doc) => {
+                print_diagnostics(&doc);
+
+                let outcome = match doc.diagnostics.worst() {
+                    Some(severity) if severity >= Severity::Error => "Finished with errors",
+                    _ => "Finished",
+                };
+                (outcome, doc.dependencies())
+            }
+            Err(_) => {
+                eprintln!("{}: {}: Processing crashed.", infile, Severity::Fatal);
+
+                // Only the files known up front can be watched after a crash.
+                let mut dependencies = BTreeSet::new();
+                dependencies.insert(PathBuf::from(infile));
+                dependencies.extend(options.md_file.map(PathBuf::from));
+                ("Crashed", dependencies)
+            }
+        };
+        println!(
+            "[{}] {} in {} ms.",
+            Local::now().format("%H:%M:%S"),
+            outcome,
+            elapsed.as_millis()
+        );
+
+        // Poll the files the last build depended on until one of them changes.
+        // Files edited while building are rebuilt right away.
+        let last_modified = modified_times(&dependencies);
+        let edited_while_building = last_modified
+            .iter()
+            .flatten()
+            .any(|modified| *modified >= started_at);
+
+        while !edited_while_building && modified_times(&dependencies) == last_modified {
+            if stop.load(Ordering::SeqCst) {
+                return;
+            }
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
+}
+
+fn handle_watch(matches: ArgMatches, md_args: &[String]) {
+    let watch_matches = matches.subcommand_matches("watch").unwrap();
+    let infile = watch_matches.value_of("infile").unwrap();
+    let outfile = watch_matches.value_of("outfile");
+    let options = BuildOptions::new(watch_matches, md_args);
+
+    println!("Watching \"{}\" for changes. Press Ctrl-C to stop.", infile);
+
+    // Watching only stops with the process.
+    let stop = AtomicBool::new(false);
+    rebuild_on_change(infile, &options, &stop, |doc| {
+        doc.preprocess();
+        doc.finish(outfile);
+    });
+}
+
+fn handle_serve(matches: ArgMatches, md_args: &[String]) {
+    let serve_matches = matches.subcommand_matches("serve").unwrap();
+    let infile = serve_matches.value_of("infile").unwrap();
+    let options = BuildOptions::new(serve_matches, md_args);
+    let port = match serve_matches.value_of("port").unwrap().parse::<u16>() {
+        Ok(port) => port,
+        Err(_) => {
+            eprintln!("The port must be a number between 0 and 65535.");
+            process::exit(Severity::Fatal.exit_code());
+        }
+    };
+
+    // Only listen on the loopback interface, the preview isn't meant to be shared.
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("Unable to listen on port {}: {}.", port, err);
+            process::exit(Severity::Fatal.exit_code());
+        }
+    };
+
+    let preview = Arc::new(Mutex::new(Preview::default()));
+    {
+        let preview = Arc::clone(&preview);
+        thread::spawn(move || server::serve(listener, preview));
+    }
+
+    println!(
+        "Serving \"{}\" on http://127.0.0.1:{}/. Press Ctrl-C to stop.",
+        infile, port
+    );
+
+    let stop = AtomicBool::new(false);
+    rebuild_on_change(infile, &options, &stop, |doc| {
+        doc.extra_scripts
+            .insert("live-reload", server::LIVE_RELOAD_SCRIPT);
+        doc.preprocess();
+
+        if let Some(html) = doc.render() {
+            preview.lock().unwrap().update(html);
+        }
+    });
+}
+
+fn handle_update(matches: ArgMatches) {
+    let update_matches = matches.subcommand_matches("update").unwrap();
+    let from = update_matches.value_of("from").unwrap();
+    // Update the spec data that would be used, unless the directory is given.
+    let spec_data_dir = match update_matches.value_of("spec-data-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let data_dirs = DataDirs::resolve("-", None, None);
+            data_dirs
+                .spec_data
+                .iter()
+                .find(|dir| dir.is_dir())
+                .unwrap_or(&data_dirs.spec_data[0])
+                .to_owned()
+        }
+    };
+
+    match update_spec_data(Path::new(from), &spec_data_dir) {
+        Ok(report) => {
+            for (kind, counts) in report {
+                println!(
+                    "Updated {}: {} added, {} removed, {} changed.",
+                    kind, counts.added, counts.removed, counts.changed
+                );
+            }
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(Severity::Fatal.exit_code());
+        }
+    }
 }
 
 pub fn run() {
     let spec_subcommand = SubCommand::with_name("spec")
-        .about("Process a spec source file into a valid output file")
+        .about("Process spec source files into valid output files")
+        .arg(
+            Arg::with_name("infile")
+                .required(true)
+                .takes_value(true)
+                .multiple(true)
+                .help(concat!(
+                    "paths to the source files, directories or glob patterns, ",
+                    "optionally followed by the output file for a single source file"
+                ))
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("print-metadata")
+                .long("print-metadata")
+                .help(concat!(
+                    "print the merged metadata and where each value came from as JSON ",
+                    "instead of writing the output file"
+                )),
+        )
+        .after_help(concat!(
+            "Metadata can be set from the command line with \"--md-<Key>=<value>\" flags, ",
+            "e.g. \"--md-status=w3c/WD\". They override the metadata in the document, ",
+            "in \"--md-file\" and in a \"metadata.json\" next to the source file.\n\n",
+            "Several specs are rendered in parallel, each one next to its source file."
+        ));
+
+    // clap can't declare flags with arbitrary names, so pull out the metadata
+    // flags before handing the rest over.
+    let (md_args, args) = split_md_args(env::args());
+
+    let watch_subcommand = SubCommand::with_name("watch")
+        .about("Process a spec source file, then rebuild it whenever its files change")
         .arg(
             Arg::with_name("infile")
                 .required(true)
@@ -38,13 +563,237 @@ pub fn run() {
                 .index(2),
         );
 
+    let serve_subcommand = SubCommand::with_name("serve")
+        .about("Serve a live preview of a spec source file on localhost")
+        .arg(
+            Arg::with_name("infile")
+                .required(true)
+                .takes_value(true)
+                .help("path to the source file")
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("port")
+                .long("port")
+                .takes_value(true)
+                .default_value("8000")
+                .help("port to listen on"),
+        );
+
+    let update_subcommand = SubCommand::with_name("update")
+        .about("Regenerate the spec data files from a local copy of the Bikeshed data")
+        .arg(
+            Arg::with_name("from")
+                .long("from")
+                .required(true)
+                .takes_value(true)
+                .help("directory or tarball with \"anchors\" and \"biblio\" data files"),
+        );
+
     let matches = App::new("bikeshed-rs")
         .version("1.0")
         .author("whichxjy")
+        .arg(
+            Arg::with_name("boilerplate-dir")
+                .long("boilerplate-dir")
+                .takes_value(true)
+                .global(true)
+                .help("directory to look for boilerplate files in first"),
+        )
+        .arg(
+            Arg::with_name("md-file")
+                .long("md-file")
+                .takes_value(true)
+                .global(true)
+                .help("JSON file with metadata that overrides the document's"),
+        )
+        .arg(
+            Arg::with_name("spec-data-dir")
+                .long("spec-data-dir")
+                .takes_value(true)
+                .global(true)
+                .help("directory to look for spec data files in first"),
+        )
         .subcommand(spec_subcommand)
-        .get_matches();
+        .subcommand(watch_subcommand)
+        .subcommand(serve_subcommand)
+        .subcommand(update_subcommand)
+        .get_matches_from(args);
+
+    match matches.subcommand_name() {
+        Some("spec") => handle_spec(matches, &md_args),
+        Some("watch") => handle_watch(matches, &md_args),
+        Some("serve") => handle_serve(matches, &md_args),
+        Some("update") => handle_update(matches),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    use super::{
+        expand_inputs, parse_md_args, rebuild_on_change, render_batch, split_md_args,
+        split_spec_paths, BuildOptions,
+    };
+    use bikeshed_rs::{Severity, SpecDataCache};
+
+    #[test]
+    fn test_parse_md_args() {
+        let md_args = vec![
+            "--md-status=w3c/WD".to_owned(),
+            "--md-date=2026-10-01".to_owned(),
+            "--md-work-status=stable".to_owned(),
+            "--md-level".to_owned(),
+            "--md-indent=x".to_owned(),
+        ];
+
+        let (md, errors) = parse_md_args(&md_args);
+        assert_eq!(md.raw_status, Some("w3c/WD".to_owned()));
+        assert_eq!(md.date.format("%Y-%m-%d").to_string(), "2026-10-01");
+        assert_eq!(md.work_status, Some("stable".to_owned()));
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_split_md_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let (md_args, other_args) = split_md_args(args(&[
+            "bikeshed-rs",
+            "spec",
+            "--md-status=LS",
+            "--md-file",
+            "--md-values.json",
+            "--md-file=md.json",
+            "foo.bs",
+            "--",
+            "--md-out.html",
+        ]));
+        assert_eq!(md_args, args(&["--md-status=LS"]));
+        assert_eq!(
+            other_args,
+            args(&[
+                "bikeshed-rs",
+                "spec",
+                "--md-file",
+                "--md-values.json",
+                "--md-file=md.json",
+                "foo.bs",
+                "--",
+                "--md-out.html",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_spec_paths() {
+        assert_eq!(
+            split_spec_paths(&["foo.bs", "out.html"]),
+            (vec!["foo.bs"], Some("out.html"))
+        );
+        assert_eq!(
+            split_spec_paths(&["foo.bs", "bar.bs"]),
+            (vec!["foo.bs", "bar.bs"], None)
+        );
+        assert_eq!(split_spec_paths(&["tests", "out.html"]).1, None);
+
+        let infiles = expand_inputs(&["tests/basic00?.bs", "foo.bs"]).unwrap();
+        assert_eq!(infiles, vec!["tests/basic001.bs", "foo.bs"]);
+        assert!(expand_inputs(&["tests/missing*.bs"]).is_err());
+        assert!(expand_inputs(&["-", "foo.bs"]).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_on_change() {
+        let infile = env::temp_dir().join(format!("bikeshed-rs-watch-{}.bs", process::id()));
+        fs::write(&infile, "Some text.\n").unwrap();
+        let infile = infile.to_string_lossy().into_owned();
+
+        let options = BuildOptions {
+            md_args: &[],
+            md_file: None,
+            boilerplate_dir: None,
+            spec_data_dir: None,
+        };
+        let stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|scope| {
+            let watcher = scope.spawn(|| {
+                let mut builds = 0;
+
+                rebuild_on_change(&infile, &options, &stop, |_| {
+                    builds += 1;
+                    let _ = sender.send(builds);
+
+                    if builds == 1 {
+                        panic!("The first build crashes.");
+                    }
+                });
+            });
+
+            let timeout = Duration::from_secs(10);
+            let first_build = receiver.recv_timeout(timeout);
+
+            // Touching the source file rebuilds it, even though the last build crashed.
+            let file = fs::OpenOptions::new().write(true).open(&infile).unwrap();
+            let modified = SystemTime::now() + Duration::from_secs(60);
+            file.set_modified(modified).unwrap();
+            let second_build = receiver.recv_timeout(timeout);
+
+            // Stop the watcher before checking, a failed check would wait on it forever.
+            stop.store(true, Ordering::SeqCst);
+            watcher.join().unwrap();
+
+            assert_eq!(first_build, Ok(1));
+            assert_eq!(second_build, Ok(2));
+        });
+
+        fs::remove_file(&infile).unwrap();
+    }
+
+    #[test]
+    fn test_render_batch() {
+        let base_dir = env::temp_dir().join(format!("bikeshed-rs-batch-{}", process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+
+        let source = fs::read_to_string("tests/basic001.bs").unwrap();
+        let infiles = vec![
+            base_dir.join("a.bs").to_string_lossy().into_owned(),
+            base_dir.join("b.bs").to_string_lossy().into_owned(),
+        ];
+        // Both specs link to the same term, the second one also to a missing one.
+        fs::write(&infiles[0], format!("{}\n<a>light source</a>\n", source)).unwrap();
+        fs::write(
+            &infiles[1],
+            format!("{}\n<a>light source</a> <a>lighter source</a>\n", source),
+        )
+        .unwrap();
+
+        let options = BuildOptions {
+            md_args: &[],
+            md_file: None,
+            boilerplate_dir: None,
+            spec_data_dir: None,
+        };
+        let spec_data_cache = SpecDataCache::default();
+        assert_eq!(
+            render_batch(&infiles, &options, &spec_data_cache),
+            Some(Severity::Error)
+        );
+        assert!(base_dir.join("a.html").is_file());
+        assert!(base_dir.join("b.html").is_file());
+        // The anchors file of the term is shared by both specs.
+        assert_eq!(spec_data_cache.anchors.file_count(), 1);
 
-    if let Some("spec") = matches.subcommand_name() {
-        handle_spec(matches);
+        fs::remove_dir_all(&base_dir).unwrap();
     }
 }
@@ -55,7 +55,62 @@ lazy_static! {
     };
     pub static ref DFN_CLASS_TO_TYPE: HashMap<&'static str, &'static str> = hashmap! {
         "propdef" => "property",
+        "valdef" => "value",
+        "at-ruledef" => "at-rule",
         "descdef" => "descriptor",
+        "typedef" => "type",
+        "funcdef" => "function",
+        "selectordef" => "selector",
+        "elementdef" => "element",
+        "element-attrdef" => "element-attr",
+        "attr-valuedef" => "attr-value",
+        "element-statedef" => "element-state",
+        "eventdef" => "event",
+        "interfacedef" => "interface",
+        "namespacedef" => "namespace",
+        "extendedattrdef" => "extended-attribute",
+        "constructordef" => "constructor",
+        "methoddef" => "method",
+        "argdef" => "argument",
+        "attrdef" => "attribute",
+        "callbackdef" => "callback",
+        "dictdef" => "dictionary",
+        "dict-memberdef" => "dict-member",
+        "enumdef" => "enum",
+        "enum-valuedef" => "enum-value",
+        "exceptiondef" => "exception",
+        "constdef" => "const",
+        "typedefdef" => "typedef",
+        "stringdef" => "stringifier",
+        "serialdef" => "serializer",
+        "iterdef" => "iterator",
+        "mapdef" => "maplike",
+        "setdef" => "setlike",
+        "grammardef" => "grammar",
+        "schemedef" => "scheme",
+        "statedef" => "state",
+        "modedef" => "mode",
+        "contextdef" => "context",
+        "facetdef" => "facet",
+        "http-headerdef" => "http-header",
+        "permissiondef" => "permission",
+    };
+    pub static ref MARKUP_TYPES: HashSet<&'static str> = hashset! {
+        "element", "element-attr", "element-state", "attr-value",
+    };
+    pub static ref IDL_TYPES: HashSet<&'static str> = hashset! {
+        "event", "interface", "namespace", "extended-attribute", "constructor", "method",
+        "argument", "attribute", "callback", "dictionary", "dict-member", "enum", "enum-value",
+        "exception", "const", "typedef", "stringifier", "serializer", "iterator", "maplike",
+        "setlike",
+    };
+    // IDL types whose dfns are identified by "dom-" ids
+    pub static ref IDL_MEMBER_TYPES: HashSet<&'static str> = hashset! {
+        "constructor", "method", "argument", "attribute", "dict-member", "enum-value", "const",
+        "stringifier", "serializer", "iterator", "maplike", "setlike",
+    };
+    pub static ref FUNCTIONISH_TYPES: HashSet<&'static str> = hashset! {
+        "function", "method", "constructor", "stringifier",
     };
     pub static ref DFN_TYPE_TO_CLASS: HashMap<&'static str, &'static str> = {
         let mut dfn_type_to_class: HashMap<&'static str, &'static str> = HashMap::new();
@@ -160,4 +215,49 @@ lazy_static! {
         "fido/FD" => "Final Document",
         "khronos/ED" => "Editor's Draft"
     };
+    // statuses that don't need an "ED" metadata
+    pub static ref NO_ED_STATUSES: HashSet<&'static str> = hashset! {
+        "LS", "LS-COMMIT", "LS-BRANCH", "LD", "FINDING", "DRAFT-FINDING", "DREAM", "iso/NP",
+        "whatwg/RD",
+    };
+    // statuses that don't need a "Level" metadata
+    pub static ref UNLEVELLED_STATUSES: HashSet<&'static str> = hashset! {
+        "LS", "LD", "DREAM", "w3c/UD", "LS-COMMIT", "LS-BRANCH", "FINDING", "DRAFT-FINDING",
+        "whatwg/RD",
+    };
+    // statuses of documents published on /TR
+    pub static ref TR_STATUSES: HashSet<&'static str> = hashset! {
+        "w3c/WD", "w3c/FPWD", "w3c/LCWD", "w3c/CR", "w3c/PR", "w3c/REC", "w3c/PER",
+        "w3c/WG-NOTE", "w3c/IG-NOTE", "w3c/NOTE",
+    };
+    // groups whose specs are always levelled
+    pub static ref LEVELLED_GROUPS: HashSet<&'static str> = hashset! {
+        "csswg", "fxtf", "houdini",
+    };
+    // megagroup => groups, used to tell which statuses a group may use
+    pub static ref MEGA_GROUPS: HashMap<&'static str, HashSet<&'static str>> = hashmap! {
+        "w3c" => hashset! {
+            "act-rules-format", "audiowg", "csswg", "dap", "fxtf", "geolocation", "houdini",
+            "html", "httpslocal", "i18n", "immersivewebwg", "mediacapture", "mediawg", "ping",
+            "privacycg", "ricg", "sacg", "secondscreenwg", "serviceworkers", "svg", "tag",
+            "texttracks", "uievents", "wasm", "web-bluetooth-cg", "web-payments", "webapps",
+            "webappsec", "webauthn", "webgpu", "webml", "webperf", "webplatform", "webrtc",
+            "webspecs", "webvr", "wicg",
+        },
+        "whatwg" => hashset! {"whatwg"},
+        "tc39" => hashset! {"tc39"},
+        "iso" => hashset! {"wg14", "wg21"},
+        "fido" => hashset! {"fido"},
+        "khronos" => hashset! {"webgl"},
+    };
+}
+
+// Find the megagroup (e.g. "w3c") that a group belongs to.
+pub fn megagroup_of(group: &str) -> Option<&'static str> {
+    let group = group.to_lowercase();
+
+    MEGA_GROUPS
+        .iter()
+        .find(|(_, groups)| groups.contains(group.as_str()))
+        .map(|(megagroup, _)| *megagroup)
 }
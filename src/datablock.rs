@@ -1,32 +1,77 @@
+use indexmap::map::IndexMap;
 use regex::Regex;
+use serde_json::{self, Map, Value};
 use std::collections::HashMap;
+use std::fmt;
 
 use crate::config;
+use crate::html;
 use crate::line::Line;
+use crate::link::biblio::{Biblio, BiblioFormat};
+use crate::link::reference::manager::LinkDefault;
 use crate::link::reference::Reference;
 use crate::spec::Spec;
 use crate::util;
+use crate::util::message::{Diagnostics, Severity};
 
 pub fn transform_data_blocks(doc: &mut Spec, lines: &[Line]) -> Vec<Line> {
     lazy_static! {
         // regex for begin tag
-        static ref BEGIN_TAG_REG: Regex = Regex::new(r"<pre [^>]*class=[^>]*anchors[^>]*>").unwrap();
-        // regex for </pre> end tag
-        static ref END_TAG_REG: Regex = Regex::new(r"</pre>\s*").unwrap();
+        static ref BEGIN_TAG_REG: Regex = Regex::new(concat!(
+            r"<(?P<tag>pre|xmp) [^>]*class=[^>]*",
+            r"\b(?P<type>anchors|biblio|link-defaults|ignored-specs|",
+            r"propdef|descdef|elementdef|argumentdef)\b[^>]*>"
+        ))
+        .unwrap();
+        // regex for </pre> or </xmp> end tag
+        static ref END_TAG_REG: Regex = Regex::new(r"</(?P<tag>pre|xmp)>\s*").unwrap();
     }
 
     let mut new_lines = Vec::new();
-    let mut in_data_block = false;
+    // the tag, the type and the begin line of the data block we are in
+    let mut data_block = None;
     let mut data_block_lines = Vec::new();
 
     for line in lines {
-        if BEGIN_TAG_REG.is_match(&line.text) && !in_data_block {
+        let in_data_block = data_block.is_some();
+
+        if !in_data_block && BEGIN_TAG_REG.is_match(&line.text) {
             // Meet begin tag.
-            in_data_block = true;
-        } else if END_TAG_REG.is_match(&line.text) && in_data_block {
+            let caps = BEGIN_TAG_REG.captures(&line.text).unwrap();
+            data_block = Some((
+                caps["tag"].to_owned(),
+                caps["type"].to_owned(),
+                line.clone(),
+            ));
+        } else if in_data_block
+            && END_TAG_REG
+                .captures(&line.text)
+                .is_some_and(|caps| data_block.as_ref().unwrap().0 == caps["tag"])
+        {
             // Meet end tag.
-            in_data_block = false;
-            transform_anchors(doc, &data_block_lines);
+            let (_, block_type, begin_line) = data_block.take().unwrap();
+            match block_type.as_str() {
+                "anchors" => transform_anchors(doc, &data_block_lines),
+                "biblio" => transform_biblio(doc, &data_block_lines, begin_line.index),
+                "link-defaults" => transform_link_defaults(doc, &data_block_lines),
+                "ignored-specs" => transform_ignored_specs(doc, &data_block_lines),
+                "propdef" | "descdef" => new_lines.extend(transform_def_table(
+                    doc,
+                    &block_type,
+                    &begin_line,
+                    &data_block_lines,
+                )),
+                "elementdef" => {
+                    new_lines.extend(transform_elementdef(doc, &begin_line, &data_block_lines))
+                }
+                "argumentdef" => new_lines.extend(transform_argumentdef(
+                    doc,
+                    &begin_line,
+                    &data_block_lines,
+                    lines,
+                )),
+                _ => unreachable!(),
+            }
             data_block_lines.clear();
         } else if in_data_block {
             // Handle line in data block.
@@ -40,12 +85,842 @@ pub fn transform_data_blocks(doc: &mut Spec, lines: &[Line]) -> Vec<Line> {
     new_lines
 }
 
+// Get the value of an attribute of the begin tag of a data block.
+fn get_begin_tag_attr(begin_line: &Line, attr: &str) -> Option<String> {
+    lazy_static! {
+        // regex for the begin tag
+        static ref TAG_REG: Regex =
+            Regex::new(r#"<(?:pre|xmp)\s(?:"[^"]*"|'[^']*'|[^"'>])*>"#).unwrap();
+        // regex for an attribute, the value is optional
+        static ref ATTR_REG: Regex = Regex::new(
+            r#"\s(?P<name>[^\s=>]+)(?:=(?:"(?P<dq>[^"]*)"|'(?P<sq>[^']*)'|(?P<uq>[^\s>]+)))?"#
+        )
+        .unwrap();
+    }
+
+    let tag = TAG_REG.find(&begin_line.text)?.as_str();
+
+    ATTR_REG
+        .captures_iter(tag)
+        .find(|caps| caps["name"].eq_ignore_ascii_case(attr))
+        .map(|caps| {
+            ["dq", "sq", "uq"]
+                .iter()
+                .find_map(|name| caps.name(name))
+                .map(|m| m.as_str().to_owned())
+                .unwrap_or_default()
+        })
+}
+
 fn transform_anchors(doc: &mut Spec, lines: &[Line]) {
-    let anchors = parse_info_tree(lines, doc.md.indent());
+    let anchors = parse_info_tree(lines, doc.md.indent(), &mut doc.diagnostics);
     process_anchors(doc, &anchors);
 }
 
-fn process_anchors(doc: &mut Spec, anchors: &[HashMap<String, Vec<String>>]) {
+lazy_static! {
+    // def table key => the definition of the key
+    static ref DEF_KEY_LINKS: HashMap<&'static str, &'static str> = hashmap! {
+        "Value" => "https://www.w3.org/TR/css-values/#value-defs",
+        "New values" => "https://www.w3.org/TR/css-values/#value-defs",
+        "Initial" => "https://www.w3.org/TR/css-cascade/#initial-values",
+        "Inherited" => "https://www.w3.org/TR/css-cascade/#inherited-property",
+        "Percentages" => "https://www.w3.org/TR/css-values/#percentages",
+        "Computed value" => "https://www.w3.org/TR/css-cascade/#computed",
+        "Animatable" => "https://www.w3.org/TR/web-animations/#animation-type",
+        "Animation type" => "https://www.w3.org/TR/web-animations/#animation-type",
+    };
+}
+
+// Turn a propdef or descdef block into a definition table.
+fn transform_def_table(
+    doc: &mut Spec,
+    block_type: &str,
+    begin_line: &Line,
+    lines: &[Line],
+) -> Vec<Line> {
+    let vals = parse_def_block(lines, &mut doc.diagnostics);
+    let classes = get_begin_tag_attr(begin_line, "class").unwrap_or_default();
+    let has_class = |class: &str| classes.split_whitespace().any(|token| token == class);
+    let partial = has_class("partial") || vals.contains_key("New values");
+
+    let shorthand = has_class("shorthand");
+
+    // key => the value used when the key is left out, if it's optional
+    let mut keys: Vec<(&str, Option<&str>)> = match (block_type, partial) {
+        ("propdef", true) => vec![("Name", None), ("New values", None)],
+        ("propdef", false) if shorthand => {
+            let mut keys = vec![("Name", None), ("Value", None)];
+            for key in [
+                "Initial",
+                "Applies to",
+                "Inherited",
+                "Percentages",
+                "Computed value",
+                "Animation type",
+            ]
+            .iter()
+            {
+                keys.push((*key, Some("see individual properties")));
+            }
+            keys
+        }
+        ("propdef", false) => vec![
+            ("Name", None),
+            ("Value", None),
+            ("Initial", None),
+            ("Applies to", None),
+            ("Inherited", None),
+            ("Percentages", None),
+            ("Computed value", None),
+        ],
+        (_, true) => vec![("Name", None), ("For", None), ("New values", None)],
+        (_, false) => vec![
+            ("Name", None),
+            ("For", None),
+            ("Value", None),
+            ("Initial", None),
+        ],
+    };
+    if block_type == "propdef" && !partial {
+        keys.push(("Canonical order", Some("per grammar")));
+    }
+
+    let mut classes = vec!["def", block_type];
+    if partial {
+        classes.push("partial");
+    }
+
+    let mut attrs = Vec::new();
+    if block_type == "propdef" {
+        if let Some(name) = vals.get("Name") {
+            let name = name.split(',').next().unwrap_or_default().trim();
+            attrs.push(format!(
+                "data-link-for-hint=\"{}\"",
+                html::escape_attr(name)
+            ));
+        }
+    }
+
+    let (name_type, name_for) = if block_type == "propdef" {
+        ("property", None)
+    } else {
+        ("descriptor", vals.get("For").map(String::as_str))
+    };
+
+    let mut table = vec![format!(
+        "<table class=\"{}\"{}>",
+        classes.join(" "),
+        attrs
+            .iter()
+            .map(|attr| format!(" {}", attr))
+            .collect::<String>()
+    )];
+
+    // A partial table adds to a definition made elsewhere, so it links to it.
+    let name_el = |name: &str| {
+        let (tag, kind) = if partial {
+            ("a", "link")
+        } else {
+            ("dfn", "dfn")
+        };
+        let for_attr = match name_for {
+            Some(name_for) => format!(" data-{}-for=\"{}\"", kind, html::escape_attr(name_for)),
+            None => String::new(),
+        };
+        format!(
+            "<{tag} data-{kind}-type=\"{}\"{}>{}</{tag}>",
+            name_type,
+            for_attr,
+            name.trim(),
+            tag = tag,
+            kind = kind
+        )
+    };
+
+    let mut add_row = |key: &str, val: &str| {
+        let th = match DEF_KEY_LINKS.get(key) {
+            Some(url) => format!("<a href=\"{}\">{}:</a>", url, key),
+            None => format!("{}:", key),
+        };
+
+        let row = match key {
+            "Name" => format!(
+                "<tr><th>{}<td>{}",
+                th,
+                val.split(',')
+                    .map(name_el)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            // Value grammars are processed like the other text.
+            "Value" | "New values" => {
+                format!("<tr class=\"value\"><th>{}<td class=\"prod\">{}", th, val)
+            }
+            _ => format!("<tr><th>{}<td>{}", th, val),
+        };
+
+        table.push(row);
+    };
+
+    for (key, default_val) in keys.iter() {
+        match vals.get(*key).map(String::as_str).or(*default_val) {
+            Some(val) => add_row(key, val),
+            None => doc.diagnostics.error(
+                format!(
+                    "The {} for \"{}\" is missing a \"{}\" line.",
+                    block_type,
+                    vals.get("Name").map_or("???", String::as_str),
+                    key
+                ),
+                Some(begin_line.index),
+            ),
+        }
+    }
+
+    // The other keys follow in the order they were given.
+    for (key, val) in vals.iter() {
+        if keys.iter().all(|(known_key, _)| known_key != key) {
+            add_row(key, val);
+        }
+    }
+
+    table.push("</table>".to_owned());
+
+    table
+        .into_iter()
+        .map(|text| Line {
+            index: begin_line.index,
+            text,
+        })
+        .collect()
+}
+
+fn transform_elementdef(doc: &mut Spec, begin_line: &Line, lines: &[Line]) -> Vec<Line> {
+    // Keys are only capitalized on their first letter, e.g. "Content model".
+    let vals = parse_def_block(lines, &mut doc.diagnostics)
+        .into_iter()
+        .map(|(key, val)| {
+            let mut chars = key.chars();
+            let key = match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => key,
+            };
+            (key, val)
+        })
+        .collect::<IndexMap<String, String>>();
+
+    let split = |val: &str| {
+        val.split(',')
+            .map(|piece| piece.trim().to_owned())
+            .filter(|piece| !piece.is_empty())
+            .collect::<Vec<String>>()
+    };
+
+    // A "Name" line without any names counts as missing.
+    let names = vals.get("Name").map(|name| split(name)).unwrap_or_default();
+    if names.is_empty() {
+        doc.diagnostics.error(
+            "The elementdef is missing a \"Name\" line.",
+            Some(begin_line.index),
+        );
+        return Vec::new();
+    }
+
+    // The attribute groups are filled in once their attributes are defined.
+    let attributes = {
+        let mut items = Vec::new();
+
+        for group in vals
+            .get("Attribute groups")
+            .map(|val| split(val))
+            .unwrap_or_default()
+        {
+            items.push(format!(
+                concat!(
+                    "<li><details data-attribute-group=\"{}\" data-element=\"{}\">",
+                    "<summary><a data-link-type=\"dfn\">{}</a></summary></details>"
+                ),
+                html::escape_attr(group.as_str()),
+                html::escape_attr(names[0].as_str()),
+                group
+            ));
+        }
+
+        for attribute in vals
+            .get("Attributes")
+            .map(|val| split(val))
+            .unwrap_or_default()
+        {
+            items.push(format!(
+                "<li><a data-link-type=\"element-attr\" data-link-for=\"{}\">{}</a>",
+                html::escape_attr(names[0].as_str()),
+                attribute
+            ));
+        }
+
+        format!("<ul>{}</ul>", items.concat())
+    };
+    let mut added_attributes = false;
+
+    let mut table = vec!["<table class=\"def elementdef\">".to_owned()];
+
+    for (key, val) in vals.iter() {
+        let row = match key.as_str() {
+            "Name" => format!(
+                "<tr><th>Name:<td>{}",
+                names
+                    .iter()
+                    .map(|name| {
+                        format!("<dfn data-dfn-type=\"element\"><code>{}</code></dfn>", name)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            "Categories" => format!(
+                "<tr><th>Categories:<td>{}",
+                split(val)
+                    .iter()
+                    .map(|category| format!("<a data-link-type=\"dfn\">{}</a>", category))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            "Attributes" | "Attribute groups" => {
+                if added_attributes {
+                    continue;
+                }
+                added_attributes = true;
+                format!("<tr><th>Attributes:<td>{}", attributes)
+            }
+            "Dom interfaces" => format!(
+                "<tr><th>DOM Interfaces:<td>{}",
+                split(val)
+                    .iter()
+                    .map(|interface| {
+                        format!("<a data-link-type=\"interface\">{}</a>", interface)
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            _ => format!("<tr><th>{}:<td>{}", key, val),
+        };
+
+        table.push(row);
+    }
+
+    table.push("</table>".to_owned());
+
+    table
+        .into_iter()
+        .map(|text| Line {
+            index: begin_line.index,
+            text,
+        })
+        .collect()
+}
+
+fn transform_argumentdef(
+    doc: &mut Spec,
+    begin_line: &Line,
+    lines: &[Line],
+    doc_lines: &[Line],
+) -> Vec<Line> {
+    lazy_static! {
+        // regex for the method the arguments are for, e.g. "Foo/get(a)"
+        static ref METHOD_REG: Regex =
+            Regex::new(r"^(?P<interface>.+)/(?P<method>[\w-]+)\s*\(.*\)$").unwrap();
+    }
+
+    let method_for = match get_begin_tag_attr(begin_line, "for") {
+        Some(method_for) => method_for.trim().to_owned(),
+        None => {
+            doc.diagnostics.error(
+                "Argumentdef blocks need a for='' attribute specifying their method.",
+                Some(begin_line.index),
+            );
+            return Vec::new();
+        }
+    };
+
+    let (interface, method) = match METHOD_REG.captures(&method_for) {
+        Some(caps) => (caps["interface"].to_owned(), caps["method"].to_owned()),
+        None => {
+            doc.diagnostics.error(
+                format!(
+                    "Argumentdef for='' must look like \"Interface/method(args)\". Got: {}.",
+                    method_for
+                ),
+                Some(begin_line.index),
+            );
+            return Vec::new();
+        }
+    };
+
+    let idl_args = find_idl_arguments(doc_lines, &interface, &method);
+    if idl_args.is_none() {
+        doc.diagnostics.error(
+            format!(
+                "Can't find the method \"{}\" of \"{}\" in the IDL blocks.",
+                method, interface
+            ),
+            Some(begin_line.index),
+        );
+    }
+
+    let mark = |flag: bool| {
+        if flag {
+            "<span class=\"yes\">✔</span>"
+        } else {
+            "<span class=\"no\">✘</span>"
+        }
+    };
+
+    let mut table = vec![
+        "<table class=\"argumentdef data\">".to_owned(),
+        format!(
+            "<caption>Arguments for the <code>{}.{}</code> method.</caption>",
+            html::escape_html(interface.as_str()),
+            html::escape_html(method_for[interface.len() + 1..].trim())
+        ),
+        "<thead><tr><th>Parameter<th>Type<th>Nullable<th>Optional<th>Description".to_owned(),
+        "<tbody>".to_owned(),
+    ];
+
+    for (name, desc) in parse_def_block(lines, &mut doc.diagnostics) {
+        let arg = idl_args
+            .as_ref()
+            .and_then(|idl_args| idl_args.iter().find(|arg| arg.name == name));
+
+        let cells = match arg {
+            Some(arg) => format!(
+                "<td>{}<td>{}<td>{}",
+                html::escape_html(arg.arg_type.as_str()),
+                mark(arg.nullable),
+                mark(arg.optional)
+            ),
+            None => {
+                if idl_args.is_some() {
+                    doc.diagnostics.error(
+                        format!(
+                            "The method \"{}\" of \"{}\" has no argument \"{}\".",
+                            method, interface, name
+                        ),
+                        Some(begin_line.index),
+                    );
+                }
+                "<td><td><td>".to_owned()
+            }
+        };
+
+        table.push(format!(
+            concat!(
+                "<tr><td><dfn data-dfn-type=\"argument\" data-dfn-for=\"{}\" id=\"dom-{}-{}\">",
+                "{}</dfn>{}<td>{}"
+            ),
+            html::escape_attr(method_for.as_str()),
+            config::generate_name(&method_for),
+            config::generate_name(&name),
+            name,
+            cells,
+            desc
+        ));
+    }
+
+    table.push("</table>".to_owned());
+
+    table
+        .into_iter()
+        .map(|text| Line {
+            index: begin_line.index,
+            text,
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct IdlArgument {
+    name: String,
+    arg_type: String,
+    nullable: bool,
+    optional: bool,
+}
+
+// Find the arguments of a method in the IDL blocks of the document.
+fn find_idl_arguments(lines: &[Line], interface: &str, method: &str) -> Option<Vec<IdlArgument>> {
+    lazy_static! {
+        // regex for begin tag of IDL block
+        static ref IDL_BEGIN_TAG_REG: Regex =
+            Regex::new(r"<(?P<tag>pre|xmp) [^>]*class=[^>]*\bidl\b[^>]*>").unwrap();
+    }
+
+    let mut idl = String::new();
+    let mut idl_tag: Option<String> = None;
+
+    for line in lines {
+        match idl_tag {
+            Some(ref tag) if line.text.contains(&format!("</{}>", tag)) => idl_tag = None,
+            Some(_) => {
+                idl.push_str(&line.text);
+                idl.push('\n');
+            }
+            None => {
+                if let Some(caps) = IDL_BEGIN_TAG_REG.captures(&line.text) {
+                    idl_tag = Some(caps["tag"].to_owned());
+                }
+            }
+        }
+    }
+
+    let idl = idl
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&");
+
+    let interface_reg = Regex::new(&format!(
+        r"\b(?:interface|namespace|dictionary)(?:\s+mixin)?\s+{}\b[^{{]*\{{",
+        regex::escape(interface)
+    ))
+    .unwrap();
+    let method_reg = Regex::new(&format!(r"\b{}\s*\(", regex::escape(method))).unwrap();
+
+    for interface_match in interface_reg.find_iter(&idl) {
+        let body = &idl[interface_match.end()..];
+        let body = &body[..body.find("};").unwrap_or(body.len())];
+
+        if let Some(method_match) = method_reg.find(body) {
+            let rest = &body[method_match.end()..];
+            let args = &rest[..find_closing_paren(rest)?];
+            return Some(
+                split_top_level(args)
+                    .iter()
+                    .filter(|arg| !arg.trim().is_empty())
+                    .map(|arg| parse_idl_argument(arg))
+                    .collect(),
+            );
+        }
+    }
+
+    None
+}
+
+fn find_closing_paren(text: &str) -> Option<usize> {
+    let mut depth = 0;
+
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(i),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Split the text on the commas that aren't nested in brackets.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                pieces.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&text[start..]);
+
+    pieces
+}
+
+fn parse_idl_argument(arg: &str) -> IdlArgument {
+    let mut arg = arg.trim();
+
+    // Skip the extended attributes.
+    if arg.starts_with('[') {
+        if let Some(end) = arg.find(']') {
+            arg = arg[end + 1..].trim_start();
+        }
+    }
+
+    let optional = arg.starts_with("optional ");
+    if optional {
+        arg = arg["optional ".len()..].trim_start();
+    }
+
+    // Skip the default value.
+    if let Some(default) = split_top_level(arg)[0].find('=') {
+        arg = arg[..default].trim_end();
+    }
+
+    let (arg_type, name) = match arg.rfind(char::is_whitespace) {
+        Some(i) => (arg[..i].trim(), arg[i..].trim()),
+        None => ("", arg),
+    };
+    let nullable = arg_type.ends_with('?');
+
+    IdlArgument {
+        name: name.trim_start_matches('_').to_owned(),
+        arg_type: arg_type.trim_end_matches('?').to_owned(),
+        nullable,
+        optional,
+    }
+}
+
+// Parse the "key: value" lines of a definition block. A line without a key
+// continues the value of the previous line.
+fn parse_def_block(lines: &[Line], diagnostics: &mut Diagnostics) -> IndexMap<String, String> {
+    lazy_static! {
+        // regex for key-value pair
+        static ref PAIR_REG: Regex = Regex::new(r"^\s*(?P<key>[\w][\w\s-]*):\s*(?P<val>.*)$").unwrap();
+    }
+
+    let mut vals: IndexMap<String, String> = IndexMap::new();
+    let mut last_key: Option<String> = None;
+
+    for line in lines {
+        if line.text.trim().is_empty() {
+            continue;
+        }
+
+        match PAIR_REG.captures(&line.text) {
+            Some(caps) => {
+                let key = caps["key"].trim().to_owned();
+                let val = caps["val"].trim();
+
+                vals.entry(key.clone())
+                    .and_modify(|prev_val| {
+                        prev_val.push('\n');
+                        prev_val.push_str(val);
+                    })
+                    .or_insert_with(|| val.to_owned());
+                last_key = Some(key);
+            }
+            None => match last_key {
+                Some(ref key) => {
+                    let prev_val = vals.get_mut(key).unwrap();
+                    prev_val.push(' ');
+                    prev_val.push_str(line.text.trim());
+                }
+                None => diagnostics.error(
+                    format!(
+                        "Line doesn't match the grammar \"key: value\": {}.",
+                        line.text.trim()
+                    ),
+                    Some(line.index),
+                ),
+            },
+        }
+    }
+
+    vals
+}
+
+fn transform_link_defaults(doc: &mut Spec, lines: &[Line]) {
+    for info in parse_info_tree(lines, doc.md.indent(), &mut doc.diagnostics) {
+        match parse_link_default(&info) {
+            Ok((link_texts, link_default)) => {
+                for link_text in link_texts {
+                    doc.reference_manager
+                        .add_link_default(link_text, link_default.clone());
+                }
+            }
+            Err(message) => doc.diagnostics.error(message, Some(info.line)),
+        }
+    }
+}
+
+fn parse_link_default(info: &Info) -> Result<(Vec<String>, LinkDefault), String> {
+    let spec = match info.get("spec") {
+        [spec] => spec.to_owned(),
+        _ => {
+            return Err(format!(
+                "Each link default needs exactly one \"spec\". Got: {}.",
+                info
+            ))
+        }
+    };
+
+    let link_type = match info.get("type") {
+        [link_type] => link_type.to_lowercase(),
+        _ => {
+            return Err(format!(
+                "Each link default needs exactly one \"type\". Got: {}.",
+                info
+            ))
+        }
+    };
+
+    let link_texts = info.get("text");
+    if link_texts.is_empty() {
+        return Err(format!(
+            "Each link default needs at least one \"text\". Got: {}.",
+            info
+        ));
+    }
+
+    let link_fors = info
+        .get("for")
+        .iter()
+        .flat_map(|link_for| config::split_for_vals(link_for))
+        .collect();
+
+    let link_default = LinkDefault {
+        link_type,
+        spec,
+        link_fors,
+    };
+
+    Ok((link_texts.to_vec(), link_default))
+}
+
+fn transform_ignored_specs(doc: &mut Spec, lines: &[Line]) {
+    for info in parse_info_tree(lines, doc.md.indent(), &mut doc.diagnostics) {
+        let specs = info.get("spec");
+        if specs.is_empty() {
+            doc.diagnostics.error(
+                format!("Each ignored spec needs a \"spec\". Got: {}.", info),
+                Some(info.line),
+            );
+            continue;
+        }
+
+        let replaced_by = match info.get("replacedBy") {
+            [] => None,
+            [replaced_by] => Some(replaced_by.to_owned()),
+            _ => {
+                doc.diagnostics.error(
+                    format!(
+                        "Each ignored spec needs at most one \"replacedBy\". Got: {}.",
+                        info
+                    ),
+                    Some(info.line),
+                );
+                continue;
+            }
+        };
+
+        for spec in specs {
+            doc.reference_manager
+                .add_ignored_spec(spec.to_owned(), replaced_by.clone());
+        }
+    }
+}
+
+fn transform_biblio(doc: &mut Spec, lines: &[Line], begin_line: u32) {
+    let text = lines
+        .iter()
+        .map(|line| line.text.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let obj: Map<String, Value> = match serde_json::from_str(&text) {
+        Ok(Value::Object(obj)) => obj,
+        Ok(_) => {
+            doc.diagnostics
+                .error("Biblio block must be a JSON object.", Some(begin_line));
+            return;
+        }
+        Err(err) => {
+            doc.diagnostics.error(
+                format!("Biblio block isn't valid JSON: {}.", err),
+                Some(begin_line + err.line() as u32),
+            );
+            return;
+        }
+    };
+
+    for (key, val) in obj.iter() {
+        match parse_biblio(key, val) {
+            Ok(biblio) => doc.biblio_manager.add_local_biblio(biblio),
+            Err(message) => doc.diagnostics.error(message, Some(begin_line)),
+        }
+    }
+}
+
+// Parse a biblio entry, which is either a string of markup, an alias of
+// another entry, or a dictionary of fields.
+fn parse_biblio(key: &str, val: &Value) -> Result<Biblio, String> {
+    let obj = match val {
+        Value::String(data) => {
+            return Ok(Biblio {
+                biblio_format: BiblioFormat::Str,
+                link_text: key.to_owned(),
+                data: Some(data.to_owned()),
+                ..Default::default()
+            })
+        }
+        Value::Object(obj) => obj,
+        _ => {
+            return Err(format!(
+                "Biblio entry \"{}\" must be a string or an object.",
+                key
+            ))
+        }
+    };
+
+    let field = |name: &str| -> Result<Option<String>, String> {
+        match obj.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(val)) => Ok(Some(val.to_owned())),
+            Some(_) => Err(format!(
+                "Field \"{}\" of biblio entry \"{}\" must be a string.",
+                name, key
+            )),
+        }
+    };
+
+    if let Some(alias_of) = field("aliasOf")? {
+        return Ok(Biblio {
+            biblio_format: BiblioFormat::Alias,
+            link_text: key.to_owned(),
+            alias_of: Some(alias_of),
+            ..Default::default()
+        });
+    }
+
+    let title = match field("title")? {
+        Some(title) => title,
+        None => return Err(format!("Biblio entry \"{}\" needs a \"title\".", key)),
+    };
+
+    let authors = match obj.get("authors") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(Value::Array(authors)) if authors.iter().all(Value::is_string) => authors
+            .iter()
+            .filter_map(Value::as_str)
+            .map(ToOwned::to_owned)
+            .collect(),
+        Some(_) => {
+            return Err(format!(
+                "Field \"authors\" of biblio entry \"{}\" must be an array of strings.",
+                key
+            ))
+        }
+    };
+
+    Ok(Biblio {
+        biblio_format: BiblioFormat::Dict,
+        link_text: key.to_owned(),
+        date: field("date")?,
+        status: field("status")?,
+        title: Some(title),
+        url: field("href")?,
+        authors,
+        ..Default::default()
+    })
+}
+
+fn process_anchors(doc: &mut Spec, anchors: &[Info]) {
     // anchors:
     // [
     //     {
@@ -61,36 +936,140 @@ fn process_anchors(doc: &mut Spec, anchors: &[HashMap<String, Vec<String>>]) {
     // ]
 
     for anchor in anchors {
-        let link_type_vals = anchor.get("type").unwrap();
-        let link_type = link_type_vals[0].to_owned();
+        match parse_anchor(anchor) {
+            Ok((link_texts, reference)) => {
+                for link_text in link_texts {
+                    doc.reference_manager
+                        .anchor_block_reference_source
+                        .add_reference(link_text, reference.clone());
+                }
+            }
+            Err(message) => doc.diagnostics.error(message, Some(anchor.line)),
+        }
+    }
+}
 
-        let link_text_vals = anchor.get("text").unwrap();
-        let link_text = link_text_vals[0].to_owned();
+// Turn an anchor into its link texts and the reference they share.
+fn parse_anchor(anchor: &Info) -> Result<(Vec<String>, Reference), String> {
+    let link_type = match anchor.get("type") {
+        [link_type] => link_type.to_lowercase(),
+        _ => {
+            return Err(format!(
+                "Each anchor needs exactly one \"type\". Got: {}.",
+                anchor
+            ))
+        }
+    };
 
-        let url_prefix = match anchor.get("urlPrefix") {
-            Some(url_prefix_vals) => url_prefix_vals.join(""),
-            None => "".to_owned(),
-        };
+    let link_texts = anchor.get("text");
+    if link_texts.is_empty() {
+        return Err(format!(
+            "Each anchor needs at least one \"text\". Got: {}.",
+            anchor
+        ));
+    }
 
-        let name = config::generate_name(&link_text);
+    let url_prefix = anchor.get("urlPrefix").join("");
+    let url = match anchor.get("url") {
+        [] if url_prefix.is_empty() => {
+            return Err(format!(
+                "Each anchor needs a \"url\" and/or at least one \"urlPrefix\". Got: {}.",
+                anchor
+            ))
+        }
+        // Without a url, the fragment is generated from the first text.
+        [] => join_url(&url_prefix, &config::generate_name(&link_texts[0])),
+        [url] => join_url(&url_prefix, url),
+        _ => {
+            return Err(format!(
+                "Each anchor needs at most one \"url\". Got: {}.",
+                anchor
+            ))
+        }
+    };
 
-        let reference = Reference {
-            link_type,
-            spec: None,
-            status: "anchor-block".to_owned(),
-            url: format!("{}#{}", url_prefix, name),
-            // TODO: Fill link fors here.
-            link_fors: Vec::new(),
-        };
+    let spec = match anchor.get("spec") {
+        [] => None,
+        [spec] => Some(spec.to_owned()),
+        _ => {
+            return Err(format!(
+                "Each anchor needs at most one \"spec\". Got: {}.",
+                anchor
+            ))
+        }
+    };
+
+    let status = match anchor.get("status") {
+        [] => "anchor-block".to_owned(),
+        [status] if status == "current" || status == "snapshot" => status.to_owned(),
+        _ => {
+            return Err(format!(
+                "Anchor \"status\" must be \"current\" or \"snapshot\". Got: {}.",
+                anchor
+            ))
+        }
+    };
+
+    let link_fors = anchor
+        .get("for")
+        .iter()
+        .flat_map(|link_for| config::split_for_vals(link_for))
+        .collect();
+
+    let reference = Reference {
+        link_type,
+        spec,
+        status,
+        url,
+        link_fors,
+    };
+
+    Ok((link_texts.to_vec(), reference))
+}
 
-        doc.reference_manager
-            .anchor_block_reference_source
-            .add_reference(link_text, reference);
+// Join the url onto the prefix, as a fragment unless either already has one.
+fn join_url(url_prefix: &str, url: &str) -> String {
+    if url_prefix.is_empty() || url.is_empty() || url_prefix.contains('#') || url.contains('#') {
+        format!("{}{}", url_prefix, url)
+    } else {
+        format!("{}#{}", url_prefix, url)
+    }
+}
+
+// A set of info from a data block, along with the line it ends on.
+#[derive(Debug, Default, PartialEq)]
+struct Info {
+    line: u32,
+    // key => values
+    pairs: HashMap<String, Vec<String>>,
+}
+
+impl Info {
+    fn get(&self, key: &str) -> &[String] {
+        self.pairs.get(key).map_or(&[], Vec::as_slice)
+    }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut keys = self.pairs.keys().collect::<Vec<&String>>();
+        keys.sort();
+
+        let pairs = keys
+            .iter()
+            .flat_map(|key| {
+                self.pairs[*key]
+                    .iter()
+                    .map(move |val| format!("{}: {}", key, val))
+            })
+            .collect::<Vec<String>>();
+
+        write!(fmt, "\"{}\"", pairs.join("; "))
     }
 }
 
 // Parse sets of info, which can be arranged into trees.
-fn parse_info_tree(lines: &[Line], tab_size: u32) -> Vec<HashMap<String, Vec<String>>> {
+fn parse_info_tree(lines: &[Line], tab_size: u32, diagnostics: &mut Diagnostics) -> Vec<Info> {
     // Each info is a set of key-value pairs, semicolon-separated:
     // key1: val1; key2: val2; key3: val3
 
@@ -113,67 +1092,393 @@ fn parse_info_tree(lines: &[Line], tab_size: u32) -> Vec<HashMap<String, Vec<Str
         static ref PAIR_REG: Regex = Regex::new(r"(?P<key>[^:]+):\s*(?P<val>.*)").unwrap();
     }
 
-    // key => values
-    let mut info_pairs: Vec<HashMap<String, Vec<String>>> = Vec::new();
+    let mut infos = Vec::new();
 
-    let mut extend_pairs = |level_pairs: &[HashMap<String, String>]| {
-        let mut info_pair: HashMap<String, Vec<String>> = HashMap::new();
+    let mut extend_infos = |level_pairs: &[Vec<(String, String)>], line: u32| {
+        let mut info = Info {
+            line,
+            ..Default::default()
+        };
 
-        for level_pair in level_pairs {
-            for (key, val) in level_pair {
-                info_pair
-                    .entry(key.to_owned())
-                    .or_default()
-                    .push(val.to_owned());
-            }
+        for (key, val) in level_pairs.iter().flatten() {
+            info.pairs
+                .entry(key.to_owned())
+                .or_default()
+                .push(val.to_owned());
         }
 
-        info_pairs.push(info_pair);
+        infos.push(info);
     };
 
     let mut last_indent_level = -1;
-    // indent level => pair
+    let mut last_line = 0;
+    // indent level => pairs
     let mut level_pairs = Vec::new();
 
     for line in lines {
+        if line.text.trim().is_empty() {
+            continue;
+        }
+
         let indent_level = util::indent::get_indent_level(&line.text, tab_size) as i32;
 
         if indent_level >= last_indent_level + 2 {
-            die!(
-                "Line jumps {} indent levels: {}.",
-                indent_level - last_indent_level,
-                line.text
+            diagnostics.error(
+                format!(
+                    "Line jumps {} indent levels: {}.",
+                    indent_level - last_indent_level,
+                    line.text
+                ),
+                Some(line.index),
             );
+            continue;
         }
 
-        if indent_level <= last_indent_level {
-            extend_pairs(&level_pairs[..=(last_indent_level as usize)]);
-        }
+        let text = match util::indent::trim_indent(&line.text, indent_level as u32, tab_size) {
+            Ok(text) => text,
+            Err(message) => {
+                diagnostics.error(message, Some(line.index));
+                continue;
+            }
+        };
 
-        let text = util::indent::trim_indent(&line.text, indent_level as u32, tab_size);
+        let mut pairs = Vec::new();
 
-        // TODO: Support grammar like `key:val; key:val; key:val`.
-        let mut pair = HashMap::new();
+        // the column of the text within the line, counted from 1
+        let text_column = 1 + (line.text.chars().count() - text.chars().count()) as u32;
+        let mut piece_start = 0;
 
-        match PAIR_REG.captures(&text) {
-            Some(caps) => {
-                let key = caps["key"].trim().to_owned();
-                let val = caps["val"].trim().to_owned();
-                pair.insert(key, val);
+        for piece in text.split(';') {
+            let column = text_column + text[..piece_start].chars().count() as u32;
+            piece_start += piece.len() + 1;
+
+            if piece.trim().is_empty() {
+                continue;
             }
-            None => die!("Line doesn't match the grammar: \"key: value\""),
-        };
+
+            match PAIR_REG.captures(piece) {
+                Some(caps) => {
+                    let key = caps["key"].trim().to_owned();
+                    let val = caps["val"].trim().to_owned();
+                    pairs.push((key, val));
+                }
+                None => {
+                    diagnostics.report_at(
+                        Severity::Error,
+                        format!(
+                            "Piece doesn't match the grammar \"key: value\": {}.",
+                            piece.trim()
+                        ),
+                        line.index,
+                        column,
+                    );
+                }
+            };
+        }
+
+        if pairs.is_empty() {
+            continue;
+        }
+
+        if indent_level <= last_indent_level {
+            extend_infos(&level_pairs[..=(last_indent_level as usize)], last_line);
+        }
 
         if (indent_level as usize) < level_pairs.len() {
-            level_pairs[indent_level as usize] = pair;
+            level_pairs[indent_level as usize] = pairs;
         } else {
-            level_pairs.push(pair);
+            level_pairs.push(pairs);
         }
 
         last_indent_level = indent_level;
+        last_line = line.index;
+    }
+
+    if last_indent_level >= 0 {
+        extend_infos(&level_pairs[..=(last_indent_level as usize)], last_line);
+    }
+
+    infos
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{
+        find_idl_arguments, parse_anchor, parse_def_block, parse_info_tree, transform_argumentdef,
+        transform_def_table, IdlArgument, Info,
+    };
+    use crate::line::Line;
+    use crate::spec::Spec;
+    use crate::util::message::Diagnostics;
+
+    fn to_lines(text: &str) -> Vec<Line> {
+        text.lines()
+            .enumerate()
+            .map(|(index, text)| Line {
+                index: index as u32 + 1,
+                text: text.to_owned(),
+            })
+            .collect()
+    }
+
+    fn parse(text: &str) -> Vec<Info> {
+        let mut diagnostics = Diagnostics::default();
+        let infos = parse_info_tree(&to_lines(text), 4, &mut diagnostics);
+        assert!(diagnostics.worst().is_none());
+        infos
+    }
+
+    #[test]
+    fn test_parse_info_tree() {
+        let infos = parse(concat!(
+            "urlPrefix: https://example.test/foo/; spec: FOO\n",
+            "    type: dfn\n",
+            "        text: term; text: terms\n",
+            "        text: other; for: a, b\n",
+            "    type: value; url: #bar; text: bar\n",
+        ));
+
+        assert_eq!(infos.len(), 3);
+        assert_eq!(infos[0].line, 3);
+        assert_eq!(infos[0].get("spec"), ["FOO"]);
+        assert_eq!(infos[0].get("text"), ["term", "terms"]);
+        assert_eq!(infos[1].line, 4);
+        assert_eq!(infos[1].get("for"), ["a, b"]);
+        assert_eq!(infos[2].get("type"), ["value"]);
+        assert_eq!(infos[2].get("url"), ["#bar"]);
+    }
+
+    #[test]
+    fn test_parse_anchor() {
+        let infos = parse(concat!(
+            "urlPrefix: https://example.test/foo/; spec: FOO\n",
+            "    type: dfn; text: Some Term; text: terms; for: a, b\n",
+            "    type: value; urlPrefix: bar.html; url: baz; status: current; text: baz\n",
+            "    text: qux\n",
+            "    type: dfn; type: value; text: quux\n",
+        ));
+
+        let (link_texts, reference) = parse_anchor(&infos[0]).unwrap();
+        assert_eq!(link_texts, ["Some Term", "terms"]);
+        assert_eq!(reference.url, "https://example.test/foo/#some-term");
+        assert_eq!(reference.spec, Some("FOO".to_owned()));
+        assert_eq!(reference.link_fors, ["a", "b"]);
+        assert_eq!(reference.status, "anchor-block");
+
+        let (_, reference) = parse_anchor(&infos[1]).unwrap();
+        assert_eq!(reference.url, "https://example.test/foo/bar.html#baz");
+        assert_eq!(reference.status, "current");
+
+        assert_eq!(
+            parse_anchor(&infos[2]).unwrap_err(),
+            concat!(
+                "Each anchor needs exactly one \"type\". ",
+                "Got: \"spec: FOO; text: qux; urlPrefix: https://example.test/foo/\"."
+            )
+        );
+        assert_eq!(infos[3].line, 5);
+        assert!(parse_anchor(&infos[3]).is_err());
+    }
+
+    #[test]
+    fn test_parse_def_block() {
+        let mut diagnostics = Diagnostics::default();
+        let vals = parse_def_block(
+            &to_lines(concat!(
+                "Name: font-display\n",
+                "For: @font-face\n",
+                "Value: auto | block\n",
+                "    | swap\n",
+                "\n",
+                "Initial: auto\n",
+            )),
+            &mut diagnostics,
+        );
+
+        assert!(diagnostics.worst().is_none());
+        assert_eq!(
+            vals.keys().collect::<Vec<&String>>(),
+            ["Name", "For", "Value", "Initial"]
+        );
+        assert_eq!(vals["Value"], "auto | block | swap");
+
+        parse_def_block(&to_lines("auto | block"), &mut diagnostics);
+        assert!(diagnostics.worst().is_some());
+    }
+
+    #[test]
+    fn test_find_idl_arguments() {
+        let lines = to_lines(concat!(
+            "<pre class=idl>\n",
+            "interface Foo {\n",
+            "  undefined get(DOMString a, optional long? b = 0, [Clamp] sequence&lt;long> c);\n",
+            "};\n",
+            "</pre>\n",
+        ));
+
+        let arg = |name: &str, arg_type: &str, nullable: bool, optional: bool| IdlArgument {
+            name: name.to_owned(),
+            arg_type: arg_type.to_owned(),
+            nullable,
+            optional,
+        };
+
+        assert_eq!(
+            find_idl_arguments(&lines, "Foo", "get"),
+            Some(vec![
+                arg("a", "DOMString", false, false),
+                arg("b", "long", true, true),
+                arg("c", "sequence<long>", false, false),
+            ])
+        );
+        assert_eq!(find_idl_arguments(&lines, "Foo", "set"), None);
+        assert_eq!(find_idl_arguments(&lines, "Bar", "get"), None);
     }
 
-    extend_pairs(&level_pairs[..=(last_indent_level as usize)]);
+    #[test]
+    fn test_transform_argumentdef() {
+        let mut doc = Spec::default();
+        let begin_line = Line {
+            index: 1,
+            text: "<pre class=argumentdef for='Foo<i>/get(a)'>".to_owned(),
+        };
+        let table = transform_argumentdef(&mut doc, &begin_line, &to_lines("a: An arg."), &[]);
 
-    info_pairs
+        // The names from the for attribute are text, not markup.
+        assert_eq!(
+            table[1].text,
+            "<caption>Arguments for the <code>Foo&lt;i>.get(a)</code> method.</caption>"
+        );
+    }
+
+    #[test]
+    fn test_transform_def_table() {
+        let def_table = |begin_text: &str, text: &str| {
+            let mut doc = Spec::default();
+            let begin_line = Line {
+                index: 1,
+                text: begin_text.to_owned(),
+            };
+            let table = transform_def_table(&mut doc, "propdef", &begin_line, &to_lines(text))
+                .into_iter()
+                .map(|line| line.text)
+                .collect::<Vec<String>>();
+            assert!(doc.diagnostics.worst().is_none());
+            table
+        };
+
+        // The header text of each row, in order.
+        let keys = |table: &[String]| {
+            let th_reg = Regex::new(r"<th>(<a [^>]*>)?(?P<key>[^:<]+):").unwrap();
+            table
+                .iter()
+                .filter_map(|row| th_reg.captures(row))
+                .map(|caps| caps["key"].to_owned())
+                .collect::<Vec<String>>()
+        };
+
+        let table = def_table(
+            "<pre class=propdef>",
+            concat!(
+                "Name: foo\n",
+                "Value: a | b\n",
+                "Initial: a\n",
+                "Applies to: all elements\n",
+                "Inherited: no\n",
+                "Percentages: n/a\n",
+                "Computed value: as specified\n",
+                "Animation type: discrete\n",
+            ),
+        );
+        assert_eq!(
+            table[0],
+            "<table class=\"def propdef\" data-link-for-hint=\"foo\">"
+        );
+        assert_eq!(
+            table[1],
+            "<tr><th>Name:<td><dfn data-dfn-type=\"property\">foo</dfn>"
+        );
+        assert_eq!(
+            keys(&table),
+            [
+                "Name",
+                "Value",
+                "Initial",
+                "Applies to",
+                "Inherited",
+                "Percentages",
+                "Computed value",
+                "Canonical order",
+                "Animation type",
+            ]
+        );
+        assert_eq!(table[8], "<tr><th>Canonical order:<td>per grammar");
+
+        let table = def_table(
+            "<pre class=\"propdef shorthand\">",
+            "Name: foo-all\nValue: <<foo>>\n",
+        );
+        assert_eq!(
+            table[3],
+            concat!(
+                "<tr><th><a href=\"https://www.w3.org/TR/css-cascade/#initial-values\">",
+                "Initial:</a><td>see individual properties"
+            )
+        );
+        assert_eq!(
+            keys(&table),
+            [
+                "Name",
+                "Value",
+                "Initial",
+                "Applies to",
+                "Inherited",
+                "Percentages",
+                "Computed value",
+                "Animation type",
+                "Canonical order",
+            ]
+        );
+
+        let table = def_table(
+            "<pre class=\"propdef partial\">",
+            "Name: foo\nNew values: c\n",
+        );
+        assert_eq!(
+            table,
+            [
+                "<table class=\"def propdef partial\" data-link-for-hint=\"foo\">",
+                "<tr><th>Name:<td><a data-link-type=\"property\">foo</a>",
+                concat!(
+                    "<tr class=\"value\"><th>",
+                    "<a href=\"https://www.w3.org/TR/css-values/#value-defs\">New values:</a>",
+                    "<td class=\"prod\">c"
+                ),
+                "</table>",
+            ]
+        );
+
+        // Only the class tokens count, not other attributes or longer class names.
+        let table = def_table(
+            "<pre class=\"propdef not-shorthand\" id=partial-foo>",
+            concat!(
+                "Name: foo\n",
+                "Value: a\n",
+                "Initial: a\n",
+                "Applies to: all elements\n",
+                "Inherited: no\n",
+                "Percentages: n/a\n",
+                "Computed value: as specified\n",
+            ),
+        );
+        assert_eq!(
+            table[0],
+            "<table class=\"def propdef\" data-link-for-hint=\"foo\">"
+        );
+        assert!(table
+            .iter()
+            .all(|row| !row.contains("see individual properties")));
+    }
 }
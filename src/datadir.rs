@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "embedded-data")]
+mod embedded {
+    // BOILERPLATE and SPEC_DATA: (relative path, contents) of the data files
+    // compiled into the binary
+    include!(concat!(env!("OUT_DIR"), "/embedded_data.rs"));
+}
+
+#[cfg(not(feature = "embedded-data"))]
+mod embedded {
+    pub static BOILERPLATE: &[(&str, &str)] = &[];
+    pub static SPEC_DATA: &[(&str, &str)] = &[];
+}
+
+// environment variables that point to the data directories
+const BOILERPLATE_DIR_VAR: &str = "BIKESHED_RS_BOILERPLATE_DIR";
+const SPEC_DATA_DIR_VAR: &str = "BIKESHED_RS_SPEC_DATA_DIR";
+
+// Directories to look for boilerplate and spec data files in, from the most
+// to the least preferred one.
+#[derive(Debug, Clone, Default)]
+pub struct DataDirs {
+    pub boilerplate: Vec<PathBuf>,
+    pub spec_data: Vec<PathBuf>,
+}
+
+impl DataDirs {
+    // Directory Priorities:
+    // 1. [directory given on the command line]
+    // 2. [directory given by the environment variable]
+    // 3. [directory next to the input file]
+    // 4. [directory in the current working directory]
+    // 5. [directory in the user data directory]
+    // 6. [data embedded in the binary] (see read_boilerplate and find_spec_data)
+    pub fn resolve(
+        infile: &str,
+        boilerplate_dir: Option<&str>,
+        spec_data_dir: Option<&str>,
+    ) -> Self {
+        let input_dir = Path::new(infile)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let user_dir = user_data_dir();
+
+        let collect = |flag: Option<&str>, var: &str, name: &str| {
+            let mut dirs = Vec::new();
+
+            if let Some(flag) = flag {
+                dirs.push(PathBuf::from(flag));
+            }
+
+            if let Some(dir) = env::var_os(var).filter(|dir| !dir.is_empty()) {
+                dirs.push(PathBuf::from(dir));
+            }
+
+            // "-" is stdin, which isn't next to anything.
+            if infile != "-" {
+                dirs.push(input_dir.join(name));
+            }
+
+            let cwd_dir = PathBuf::from(name);
+            if !dirs.contains(&cwd_dir) {
+                dirs.push(cwd_dir);
+            }
+
+            if let Some(ref user_dir) = user_dir {
+                dirs.push(user_dir.join(name));
+            }
+
+            dirs
+        };
+
+        DataDirs {
+            boilerplate: collect(boilerplate_dir, BOILERPLATE_DIR_VAR, "boilerplate"),
+            spec_data: collect(spec_data_dir, SPEC_DATA_DIR_VAR, "spec-data"),
+        }
+    }
+
+    // Read the first existing boilerplate file, along with its path if it
+    // isn't embedded. Each directory is searched for all the candidates before
+    // moving on to the next directory.
+    pub fn read_boilerplate(&self, candidates: &[PathBuf]) -> Option<(String, Option<PathBuf>)> {
+        if let Some(path) = find_file(&self.boilerplate, candidates) {
+            if let Ok(data) = fs::read_to_string(&path) {
+                return Some((data, Some(path)));
+            }
+        }
+
+        candidates
+            .iter()
+            .find_map(|candidate| find_embedded(embedded::BOILERPLATE, candidate))
+            .map(|data| (data.to_owned(), None))
+    }
+}
+
+// A spec data file found in one of the directories or in the embedded data.
+#[derive(Debug, Clone)]
+pub enum DataFile {
+    Disk(PathBuf),
+    Embedded(PathBuf, &'static str),
+}
+
+impl DataFile {
+    // The path on disk, if the file isn't embedded.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            DataFile::Disk(path) => Some(path),
+            DataFile::Embedded(..) => None,
+        }
+    }
+
+    pub fn open(&self) -> io::Result<Box<dyn BufRead>> {
+        match self {
+            DataFile::Disk(path) => Ok(Box::new(BufReader::new(File::open(path)?))),
+            DataFile::Embedded(_, data) => Ok(Box::new(io::Cursor::new(*data))),
+        }
+    }
+
+    // A key that tells the file apart from all other data files.
+    fn cache_key(&self) -> String {
+        match self {
+            DataFile::Disk(path) => format!("disk:{}", path.display()),
+            DataFile::Embedded(rel_path, _) => format!("embedded:{}", rel_path.display()),
+        }
+    }
+}
+
+pub fn find_spec_data(dirs: &[PathBuf], rel_path: &Path) -> Option<DataFile> {
+    if let Some(path) = find_file(dirs, &[rel_path.to_path_buf()]) {
+        return Some(DataFile::Disk(path));
+    }
+
+    find_embedded(embedded::SPEC_DATA, rel_path)
+        .map(|data| DataFile::Embedded(rel_path.to_path_buf(), data))
+}
+
+// Parsed data files that can be shared between specs (and threads), so that
+// each file is only loaded once.
+#[derive(Debug)]
+pub struct DataCache<T> {
+    files: Mutex<HashMap<String, Arc<T>>>,
+}
+
+impl<T> Default for DataCache<T> {
+    fn default() -> Self {
+        DataCache {
+            files: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> DataCache<T> {
+    // The number of files loaded so far.
+    pub fn file_count(&self) -> usize {
+        self.files.lock().unwrap().len()
+    }
+
+    // Get the parsed file, loading it on the first request. Failures aren't
+    // cached. The lock isn't held while loading, so two threads may happen to
+    // load the same file, in which case the first one wins.
+    pub fn get_or_load(
+        &self,
+        data_file: &DataFile,
+        load: impl FnOnce() -> Result<T, String>,
+    ) -> Result<Arc<T>, String> {
+        let key = data_file.cache_key();
+
+        if let Some(data) = self.files.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(data));
+        }
+
+        let data = Arc::new(load()?);
+        Ok(Arc::clone(
+            self.files.lock().unwrap().entry(key).or_insert(data),
+        ))
+    }
+}
+
+fn find_embedded(files: &[(&str, &'static str)], rel_path: &Path) -> Option<&'static str> {
+    let key = rel_path
+        .iter()
+        .map(|piece| piece.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    files
+        .iter()
+        .find(|(path, _)| *path == key)
+        .map(|(_, data)| *data)
+}
+
+// Find the first existing file among the candidates in the directories.
+pub fn find_file(dirs: &[PathBuf], candidates: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter()
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .find(|path| path.is_file())
+}
+
+// The per-user data directory of bikeshed-rs, following the XDG base
+// directory spec (and %APPDATA% on Windows).
+fn user_data_dir() -> Option<PathBuf> {
+    let var = |name: &str| env::var_os(name).filter(|val| !val.is_empty());
+
+    let base_dir = if let Some(dir) = var("XDG_DATA_HOME") {
+        PathBuf::from(dir)
+    } else if let Some(dir) = var("APPDATA") {
+        PathBuf::from(dir)
+    } else {
+        PathBuf::from(var("HOME")?).join(".local").join("share")
+    };
+
+    Some(base_dir.join("bikeshed-rs"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_file, DataDirs};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_resolve() {
+        let data_dirs = DataDirs::resolve("specs/foo.bs", Some("my-boilerplate"), None);
+        assert_eq!(data_dirs.boilerplate[0], PathBuf::from("my-boilerplate"));
+        assert!(data_dirs
+            .boilerplate
+            .contains(&PathBuf::from("specs").join("boilerplate")));
+        assert!(data_dirs
+            .spec_data
+            .contains(&PathBuf::from("specs").join("spec-data")));
+        assert!(!data_dirs
+            .spec_data
+            .contains(&PathBuf::from("my-boilerplate")));
+
+        // The directory next to the input file comes before the one in the CWD.
+        let position = |path: PathBuf| data_dirs.spec_data.iter().position(|dir| *dir == path);
+        assert!(
+            position(PathBuf::from("specs").join("spec-data"))
+                < position(PathBuf::from("spec-data"))
+        );
+
+        // Stdin isn't next to anything, but the CWD is still searched.
+        let data_dirs = DataDirs::resolve("-", None, None);
+        assert!(data_dirs
+            .boilerplate
+            .contains(&PathBuf::from("boilerplate")));
+    }
+
+    #[test]
+    fn test_find_file() {
+        let dirs = vec![PathBuf::from("missing"), PathBuf::from("boilerplate")];
+        let candidates = vec![
+            PathBuf::from("csswg").join("missing.include"),
+            PathBuf::from("header.include"),
+        ];
+        assert_eq!(
+            find_file(&dirs, &candidates),
+            Some(PathBuf::from("boilerplate").join("header.include"))
+        );
+        assert_eq!(find_file(&dirs, &candidates[..1]), None);
+    }
+
+    #[cfg(feature = "embedded-data")]
+    #[test]
+    fn test_embedded() {
+        use super::find_spec_data;
+        use std::io::BufRead;
+        use std::path::Path;
+
+        let data_dirs = DataDirs::default();
+        let (data, path) = data_dirs
+            .read_boilerplate(&[PathBuf::from("header.include")])
+            .unwrap();
+        assert_eq!(
+            data,
+            std::fs::read_to_string("boilerplate/header.include").unwrap()
+        );
+        assert_eq!(path, None);
+
+        let rel_path = Path::new("anchors").join("anchors-ac.data");
+        let data_file = find_spec_data(&[], &rel_path).unwrap();
+        assert!(data_file.open().unwrap().lines().next().is_some());
+        assert_eq!(data_file.path(), None);
+    }
+}
@@ -5,6 +5,7 @@ use crate::config::{DFN_SELECTOR, DFN_TYPES, LINK_TYPES};
 use crate::html;
 use crate::spec::Spec;
 use crate::util;
+use crate::util::message::Diagnostics;
 
 // Code span manager would protect code spans from map functions.
 
@@ -95,7 +96,8 @@ impl CodeSpanManager {
         }
     }
 
-    pub fn map_text_pieces(&mut self, map_fn: impl Fn(&str) -> String) {
+    #[allow(clippy::needless_borrow)]
+    pub fn map_text_pieces(&mut self, mut map_fn: impl FnMut(&str) -> String) {
         self.text_pieces = self
             .text_pieces
             .iter()
@@ -122,7 +124,11 @@ impl CodeSpanManager {
 }
 
 // Replace macros with text.
-pub fn replace_macros<'a>(text: &str, macros: &HashMap<&'a str, String>) -> String {
+pub fn replace_macros(
+    text: &str,
+    macros: &HashMap<String, String>,
+    diagnostics: &mut Diagnostics,
+) -> String {
     lazy_static! {
         static ref REG: Regex = Regex::new(
             r"(?x)
@@ -139,15 +145,15 @@ pub fn replace_macros<'a>(text: &str, macros: &HashMap<&'a str, String>) -> Stri
 
         // Fail to find a matching macro:
         // [FOO?] => replace it with nothing
-        // [FOO] => throw a fatal error
+        // [FOO] => report a warning and leave it as it is
         let optional = match caps.name("optional") {
             Some(m) => m.as_str() == "?",
             None => false,
         };
 
-        if full_text.starts_with('\\') {
+        if let Some(text) = full_text.strip_prefix('\\') {
             // Escaped.
-            return full_text[1..].to_owned();
+            return text.to_owned();
         }
 
         if full_text.starts_with("[[") {
@@ -165,7 +171,8 @@ pub fn replace_macros<'a>(text: &str, macros: &HashMap<&'a str, String>) -> Stri
         }
 
         if !optional {
-            die!("Found unmatched text macro {}.", full_text);
+            diagnostics.warning(format!("Found unmatched text macro {}.", full_text), None);
+            return full_text.to_owned();
         }
 
         "".to_owned()
@@ -269,3 +276,33 @@ pub fn canonicalize_shortcuts(doc: &Spec) {
         html::remove_attr(&el, "for");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::replace_macros;
+    use crate::util::message::{Diagnostics, Severity};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_replace_macros() {
+        let mut macros = HashMap::new();
+        macros.insert("date".to_owned(), "1 January 1970".to_owned());
+        macros.insert("foo".to_owned(), "bar".to_owned());
+
+        let mut diagnostics = Diagnostics::default();
+        let text = replace_macros(
+            "[DATE] [Date] [DATE?] \\[DATE] [[FOO]] [FOO] [BAZ?] [123]",
+            &macros,
+            &mut diagnostics,
+        );
+        assert_eq!(
+            text,
+            "1 January 1970 [Date] 1 January 1970 [DATE] [[FOO]] bar  [123]"
+        );
+        assert_eq!(diagnostics.worst(), None);
+
+        let text = replace_macros("[BAZ]", &macros, &mut diagnostics);
+        assert_eq!(text, "[BAZ]");
+        assert_eq!(diagnostics.worst(), Some(Severity::Warning));
+    }
+}
@@ -40,6 +40,7 @@ fn wrap_heading_contents(heading_el: &NodeRef) {
 }
 
 // Insert "data-level" attribute into each heading element.
+#[allow(clippy::legacy_numeric_constants)]
 fn add_level(heading_els: &[NodeRef]) {
     fn increment_level(heading_levels: &mut [u32], level: usize) {
         heading_levels[level - 2] += 1;
@@ -47,7 +48,7 @@ fn add_level(heading_els: &[NodeRef]) {
         for heading_level in heading_levels.iter_mut().take(5).skip(level - 1) {
             *heading_level = 0;
         }
-    };
+    }
 
     fn levels_to_string(heading_levels: &[u32]) -> String {
         heading_levels
@@ -56,7 +57,7 @@ fn add_level(heading_els: &[NodeRef]) {
             .map(|l| l.to_string())
             .collect::<Vec<String>>()
             .join(".")
-    };
+    }
 
     let mut heading_levels: Vec<u32> = vec![0, 0, 0, 0, 0];
 
@@ -175,18 +175,9 @@ pub fn add_class(el: &NodeRef, class: &str) {
     attributes.insert(local_name!("class"), new_class_attr);
 }
 
-// Get the content of the text node wrapped by the given node.
+// Get the text content of the given node, including that of its descendants.
 pub fn get_text_content(el: &NodeRef) -> String {
-    for child in el.children() {
-        if let Some(content) = child.as_text() {
-            let content = content.borrow().trim().to_owned();
-
-            if !content.is_empty() {
-                return content;
-            }
-        }
-    }
-    "".to_owned()
+    el.text_contents().trim().to_owned()
 }
 
 fn is_valid(el: &NodeRef) -> bool {
@@ -197,6 +188,7 @@ fn is_valid(el: &NodeRef) -> bool {
 }
 
 // If the node only has one child, extract it.
+#[allow(clippy::redundant_closure)]
 pub fn get_only_child(el: &NodeRef) -> Option<NodeRef> {
     let children = el
         .children()
@@ -211,6 +203,7 @@ pub fn get_only_child(el: &NodeRef) -> Option<NodeRef> {
 }
 
 // Copy the content of a node to another node.
+#[allow(clippy::redundant_closure)]
 pub fn copy_content(from_el: &NodeRef, to_el: &NodeRef) {
     for from_el_child in from_el.children().filter(|el| is_valid(el)) {
         to_el.append(from_el_child.clone());
@@ -231,6 +224,10 @@ pub fn escape_html<T: Into<String>>(text: T) -> String {
     text.into().replace("&", "&amp;").replace("<", "&lt;")
 }
 
+pub fn escape_attr<T: Into<String>>(text: T) -> String {
+    escape_html(text).replace("\"", "&quot;")
+}
+
 pub fn select_first(el: &NodeRef, selectors: &str) -> Option<NodeRef> {
     match el.select_first(selectors) {
         Ok(el_data) => Some(el_data.as_node().clone()),
@@ -255,6 +252,7 @@ where
     )
 }
 
+#[allow(clippy::legacy_numeric_constants)]
 fn relevant_headings(el: &NodeRef, heading_level_tags: &HashSet<&str>) -> Vec<NodeRef> {
     let mut heading_els = Vec::new();
 
@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate markup5ever;
+#[macro_use]
+extern crate maplit;
+
+mod boilerplate;
+mod clean;
+mod config;
+mod datablock;
+mod datadir;
+mod fix;
+mod heading;
+mod html;
+mod line;
+mod link;
+mod markdown;
+pub mod metadata;
+mod shorthand;
+pub mod spec;
+#[cfg(test)]
+mod test;
+mod update;
+mod util;
+
+pub use crate::config::SOURCE_FILE_EXTENSIONS;
+pub use crate::datadir::DataDirs;
+pub use crate::link::SpecDataCache;
+pub use crate::metadata::{Metadata, MetadataSource};
+pub use crate::spec::Spec;
+pub use crate::update::{update_spec_data, UpdateCounts};
+pub use crate::util::message::{Diagnostic, Diagnostics, Severity};
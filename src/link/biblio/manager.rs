@@ -1,29 +1,50 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use super::source::{BiblioFormat, BiblioSource};
-use super::Biblio;
+use super::{Biblio, BiblioGroup};
+use crate::link::SpecDataCache;
 
 #[derive(Debug, Default)]
 pub struct BiblioManager {
     pub biblio_source: BiblioSource,
+    // biblios defined in the document, which take precedence over the spec data
+    local_biblios: BiblioGroup,
 }
 
 impl BiblioManager {
     pub fn new() -> Self {
         BiblioManager {
             biblio_source: BiblioSource::new("biblio"),
+            ..Default::default()
         }
     }
 
-    pub fn get_biblio(&mut self, link_text: &str) -> Option<Biblio> {
+    pub fn set_spec_data(&mut self, spec_data_dirs: &[PathBuf], cache: &SpecDataCache) {
+        self.biblio_source
+            .set_spec_data(spec_data_dirs, Arc::clone(&cache.biblio));
+    }
+
+    pub fn add_local_biblio(&mut self, biblio: Biblio) {
+        self.local_biblios
+            .insert(biblio.link_text.to_lowercase(), biblio);
+    }
+
+    pub fn get_biblio(&mut self, link_text: &str) -> Result<Option<Biblio>, String> {
         let link_text = link_text.to_lowercase();
 
-        if let Some(biblio) = self.biblio_source.fetch_biblio(&link_text) {
-            if biblio.biblio_format == BiblioFormat::Alias {}
-            match biblio.biblio_format {
-                BiblioFormat::Alias => return self.get_biblio(biblio.alias_of.as_ref().unwrap()),
-                _ => return Some(biblio),
-            }
-        }
+        let biblio = match self.local_biblios.get(&link_text) {
+            Some(biblio) => Some(biblio.to_owned()),
+            None => self.biblio_source.fetch_biblio(&link_text)?,
+        };
 
-        None
+        match biblio {
+            Some(Biblio {
+                biblio_format: BiblioFormat::Alias,
+                alias_of: Some(alias_of),
+                ..
+            }) => self.get_biblio(&alias_of),
+            biblio => Ok(biblio),
+        }
     }
 }
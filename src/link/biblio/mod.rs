@@ -1,10 +1,15 @@
 pub mod manager;
 mod source;
 
+use kuchiki::traits::*;
 use kuchiki::NodeRef;
+use std::collections::HashMap;
 
 use crate::html::{self, Attr};
-use source::BiblioFormat;
+pub use source::BiblioFormat;
+
+// text => biblio
+pub type BiblioGroup = HashMap<String, Biblio>;
 
 #[derive(Debug, Default, Clone)]
 pub struct Biblio {
@@ -23,14 +28,26 @@ impl Biblio {
     pub fn to_node(&self) -> NodeRef {
         let dd_el = html::new_element("dd", None::<Attr>);
 
-        let authors_text = if self.authors.len() == 1 {
-            format!("{}. ", self.authors[0])
-        } else if self.authors.len() < 4 {
-            format!("{}. ", self.authors.join("; "))
-        } else {
-            format!("{}; et al. ", self.authors[0])
-        };
-        dd_el.append(html::new_text(authors_text));
+        // String biblios are stored as ready-made markup.
+        if self.biblio_format == BiblioFormat::Str {
+            let data = self.data.to_owned().unwrap_or_default();
+            let data_dom = kuchiki::parse_html().one(data);
+
+            if let Some(body) = html::select_first(&data_dom, "body") {
+                html::copy_content(&body, &dd_el);
+            }
+
+            return dd_el;
+        }
+
+        if !self.authors.is_empty() {
+            let authors_text = if self.authors.len() < 4 {
+                format!("{}. ", self.authors.join("; "))
+            } else {
+                format!("{}; et al. ", self.authors[0])
+            };
+            dd_el.append(html::new_text(authors_text));
+        }
 
         match &self.url {
             Some(url) => dd_el.append(html::new_a(
@@ -1,8 +1,11 @@
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use super::Biblio;
+use super::{Biblio, BiblioGroup};
 use crate::config;
+use crate::datadir::{self, DataCache, DataFile};
 use crate::util::reader;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -12,6 +15,7 @@ pub enum BiblioFormat {
     Alias,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for BiblioFormat {
     fn default() -> Self {
         BiblioFormat::Dict
@@ -21,9 +25,11 @@ impl Default for BiblioFormat {
 #[derive(Debug, Default)]
 pub struct BiblioSource {
     base_path: String,
-    loaded_groups: HashSet<String>,
-    // text => biblios
-    biblios: HashMap<String, Biblio>,
+    spec_data_dirs: Vec<PathBuf>,
+    spec_data_cache: Arc<DataCache<BiblioGroup>>,
+    // group => biblios loaded from the spec data
+    loaded_groups: HashMap<String, Arc<BiblioGroup>>,
+    loaded_files: Vec<PathBuf>,
 }
 
 impl BiblioSource {
@@ -34,40 +40,96 @@ impl BiblioSource {
         }
     }
 
-    fn load(&mut self, group: &str) {
-        let data_path = Path::new("spec-data")
-            .join(&self.base_path)
-            .join(format!("biblio-{}.data", group));
+    fn load(&mut self, group: &str) -> Result<Arc<BiblioGroup>, String> {
+        let rel_path = Path::new(&self.base_path).join(format!("biblio-{}.data", group));
+
+        let data_file = match datadir::find_spec_data(&self.spec_data_dirs, &rel_path) {
+            Some(data_file) => data_file,
+            None => {
+                return Err(format!(
+                    "Fail to load biblio data file: \"{}\".",
+                    rel_path.display()
+                ))
+            }
+        };
+        self.loaded_files
+            .extend(data_file.path().map(Path::to_path_buf));
+
+        self.spec_data_cache
+            .get_or_load(&data_file, || parse_spec_data(&data_file, &rel_path))
+    }
+
+    pub fn set_spec_data(
+        &mut self,
+        spec_data_dirs: &[PathBuf],
+        spec_data_cache: Arc<DataCache<BiblioGroup>>,
+    ) {
+        self.spec_data_dirs = spec_data_dirs.to_vec();
+        self.spec_data_cache = spec_data_cache;
+    }
+
+    // The data files read so far.
+    pub fn loaded_files(&self) -> &[PathBuf] {
+        &self.loaded_files
+    }
+
+    pub fn fetch_biblio(&mut self, key: &str) -> Result<Option<Biblio>, String> {
+        let group = config::generate_group_name(key);
+
+        if !self.loaded_groups.contains_key(&group) {
+            // Mark the group as loaded even if it fails, so that the failure is only reported once.
+            let loaded = self.load(&group);
+            self.loaded_groups
+                .insert(group.clone(), loaded.clone().unwrap_or_default());
+            loaded?;
+        }
+
+        Ok(self.loaded_groups[&group].get(key).map(ToOwned::to_owned))
+    }
+}
 
-        let mut lines = match reader::read_lines(&data_path) {
-            Ok(lines) => lines,
-            _ => die!(
+fn parse_spec_data(data_file: &DataFile, rel_path: &Path) -> Result<BiblioGroup, String> {
+    let mut lines = match data_file.open() {
+        Ok(data) => data.lines(),
+        Err(_) => {
+            return Err(format!(
                 "Fail to load biblio data file: \"{}\".",
-                data_path.to_str().unwrap()
-            ),
-        };
+                rel_path.display()
+            ))
+        }
+    };
+    let mut biblios = BiblioGroup::new();
 
+    let mut parse = || -> io::Result<()> {
         while let Some(full_key) = lines.next() {
-            let full_key = full_key.unwrap();
+            let full_key = full_key?;
+
+            if full_key.len() < 2 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid biblio key: {}", full_key),
+                ));
+            }
+
             let prefix = &full_key[0..1];
             let key = full_key[2..].trim_end();
 
             let biblio = match prefix {
                 "d" => {
-                    let link_text = lines.next().unwrap().unwrap();
-                    let date = lines.next().unwrap().unwrap();
-                    let status = lines.next().unwrap().unwrap();
-                    let title = lines.next().unwrap().unwrap();
-                    let url = lines.next().unwrap().unwrap();
-                    lines.next(); // current url
-                    lines.next(); // obsoleted by
-                    lines.next(); // other
-                    lines.next(); // el at or not
+                    let link_text = reader::next_line(&mut lines)?;
+                    let date = reader::next_line(&mut lines)?;
+                    let status = reader::next_line(&mut lines)?;
+                    let title = reader::next_line(&mut lines)?;
+                    let url = reader::next_line(&mut lines)?;
+                    reader::next_line(&mut lines)?; // current url
+                    reader::next_line(&mut lines)?; // obsoleted by
+                    reader::next_line(&mut lines)?; // other
+                    reader::next_line(&mut lines)?; // el at or not
 
                     let mut authors = Vec::new();
 
                     loop {
-                        let line = lines.next().unwrap().unwrap();
+                        let line = reader::next_line(&mut lines)?;
 
                         if line == "-" {
                             break;
@@ -79,8 +141,8 @@ impl BiblioSource {
                     Biblio {
                         biblio_format: BiblioFormat::Dict,
                         link_text,
-                        date: Some(date),
-                        status: Some(status),
+                        date: Some(date).filter(|date| !date.is_empty()),
+                        status: Some(status).filter(|status| !status.is_empty()),
                         title: Some(title),
                         url: Some(url),
                         authors,
@@ -88,9 +150,9 @@ impl BiblioSource {
                     }
                 }
                 "s" => {
-                    let link_text = lines.next().unwrap().unwrap();
-                    let data = lines.next().unwrap().unwrap();
-                    lines.next();
+                    let link_text = reader::next_line(&mut lines)?;
+                    let data = reader::next_line(&mut lines)?;
+                    reader::next_line(&mut lines)?;
 
                     Biblio {
                         biblio_format: BiblioFormat::Str,
@@ -100,9 +162,9 @@ impl BiblioSource {
                     }
                 }
                 "a" => {
-                    let link_text = lines.next().unwrap().unwrap();
-                    let alias_of = lines.next().unwrap().unwrap();
-                    lines.next();
+                    let link_text = reader::next_line(&mut lines)?;
+                    let alias_of = reader::next_line(&mut lines)?;
+                    reader::next_line(&mut lines)?;
 
                     Biblio {
                         biblio_format: BiblioFormat::Alias,
@@ -111,27 +173,27 @@ impl BiblioSource {
                         ..Default::default()
                     }
                 }
-                _ => die!("Unknown biblio prefix: {}.", prefix),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unknown biblio prefix: {}", prefix),
+                    ))
+                }
             };
 
-            self.biblios.insert(key.to_owned(), biblio);
+            biblios.insert(key.to_owned(), biblio);
         }
-    }
 
-    pub fn fetch_biblio(&mut self, key: &str) -> Option<Biblio> {
-        if let Some(biblio) = self.biblios.get(key) {
-            return Some(biblio.to_owned());
-        }
-
-        let group = config::generate_group_name(key);
+        Ok(())
+    };
 
-        if self.loaded_groups.contains(group.as_str()) {
-            return None;
-        }
-
-        self.load(&group);
-        self.loaded_groups.insert(group);
+    parse().map_err(|err| {
+        format!(
+            "Fail to parse biblio data file: \"{}\" ({}).",
+            rel_path.display(),
+            err
+        )
+    })?;
 
-        self.biblios.get(key).map(ToOwned::to_owned)
-    }
+    Ok(biblios)
 }
@@ -1,40 +1,113 @@
 use kuchiki::NodeRef;
 
 use crate::config::DFN_SELECTOR;
-use crate::config::{self, DFN_TYPES, DFN_TYPE_TO_CLASS};
+use crate::config::{
+    self, DFN_TYPES, DFN_TYPE_TO_CLASS, FUNCTIONISH_TYPES, IDL_MEMBER_TYPES, IDL_TYPES,
+    MARKUP_TYPES,
+};
 use crate::html;
 use crate::spec::Spec;
+use crate::util::message::Diagnostics;
 
 pub fn process_dfns(doc: &mut Spec) {
     let dfn_els = html::select(doc.dom(), &DFN_SELECTOR).collect::<Vec<NodeRef>>();
-    classify_dfns(&dfn_els);
+    let dfn_els = classify_dfns(&dfn_els, &mut doc.diagnostics);
     html::dedup_ids(doc.dom());
     doc.reference_manager.add_local_dfns(&dfn_els);
+    fill_attribute_groups(doc.dom());
+}
+
+// List the attributes of each attribute group referenced by an elementdef. The listed
+// dfns only restate the group's ones, so they aren't classified as local dfns.
+fn fill_attribute_groups(root: &NodeRef) {
+    let attr_dfn_els = html::select(root, &DFN_SELECTOR)
+        .filter(|dfn_el| determine_dfn_type(dfn_el) == "element-attr")
+        .collect::<Vec<NodeRef>>();
+
+    let details_els = html::select(root, "details[data-attribute-group]").collect::<Vec<NodeRef>>();
+
+    for details_el in details_els {
+        let group = html::get_attr(&details_el, "data-attribute-group").unwrap();
+        let element = html::get_attr(&details_el, "data-element").unwrap_or_default();
+
+        let ul_el = html::new_element("ul", None::<html::Attr>);
+
+        for attr_dfn_el in attr_dfn_els.iter() {
+            if html::closest_attr_in(attr_dfn_el, "data-dfn-for").as_ref() != Some(&group) {
+                continue;
+            }
+
+            let attr = html::get_text_content(attr_dfn_el);
+
+            let dfn_el = html::new_element(
+                "dfn",
+                vec![
+                    ("data-dfn-type", "element-attr".to_owned()),
+                    ("for", element.to_owned()),
+                    (
+                        "id",
+                        format!(
+                            "element-attrdef-{}-{}",
+                            config::generate_name(&element),
+                            config::generate_name(&attr)
+                        ),
+                    ),
+                ],
+            );
+            dfn_el.append(html::new_a(
+                vec![
+                    ("data-link-type", "element-attr".to_owned()),
+                    ("for", group.to_owned()),
+                    ("data-link-for", group.to_owned()),
+                ],
+                attr,
+            ));
+
+            let li_el = html::new_element("li", None::<html::Attr>);
+            li_el.append(dfn_el);
+            ul_el.append(li_el);
+        }
+
+        details_el.append(ul_el);
+        html::remove_attr(&details_el, "data-attribute-group");
+        html::remove_attr(&details_el, "data-element");
+    }
 }
 
 fn determine_dfn_type(dfn_el: &NodeRef) -> String {
-    match html::get_attr(dfn_el, "data-dfn-type") {
+    // The type can be given once for all the dfns in a container.
+    match html::closest_attr_in(dfn_el, "data-dfn-type") {
         Some(dfn_type) => dfn_type,
         None => "dfn".to_owned(),
     }
 }
 
-fn classify_dfns(dfn_els: &[NodeRef]) {
+// Classify the dfns, returning those of a known type.
+fn classify_dfns(dfn_els: &[NodeRef], diagnostics: &mut Diagnostics) -> Vec<NodeRef> {
+    let mut classified_dfn_els = Vec::new();
+
     for dfn_el in dfn_els {
         let dfn_type = determine_dfn_type(dfn_el);
 
-        assert!(
-            DFN_TYPES.contains(dfn_type.as_str()),
-            "Unknown dfn type: {}.",
-            dfn_type
-        );
+        if !DFN_TYPES.contains(dfn_type.as_str()) {
+            diagnostics.error(
+                format!(
+                    "Unknown dfn type \"{}\" for the dfn of \"{}\".",
+                    dfn_type,
+                    html::get_text_content(dfn_el)
+                ),
+                None,
+            );
+            continue;
+        }
 
         if !html::has_attr(dfn_el, "data-dfn-type") {
             html::insert_attr(dfn_el, "data-dfn-type", &dfn_type);
         }
 
-        if let Some(dfn_for) = html::closest_attr_in(&dfn_el, "data-dfn-for") {
-            html::insert_attr(&dfn_el, "data-dfn-for", dfn_for);
+        let dfn_for = html::closest_attr_in(dfn_el, "data-dfn-for");
+        if let Some(ref dfn_for) = dfn_for {
+            html::insert_attr(dfn_el, "data-dfn-for", dfn_for);
         }
 
         // export or noexport
@@ -51,18 +124,64 @@ fn classify_dfns(dfn_els: &[NodeRef]) {
             }
         }
 
+        if IDL_TYPES.contains(dfn_type.as_str()) {
+            html::add_class(dfn_el, "idl-code");
+        }
+
+        // Markup and IDL names are code.
+        if MARKUP_TYPES.contains(dfn_type.as_str()) || IDL_TYPES.contains(dfn_type.as_str()) {
+            let is_code = html::get_only_child(dfn_el)
+                .and_then(|child| html::get_tag(&child))
+                .is_some_and(|tag| tag == "code");
+
+            if !is_code {
+                let code_el = html::new_element("code", None::<html::Attr>);
+                html::copy_content(dfn_el, &code_el);
+                dfn_el.append(code_el);
+            }
+        }
+
         // Fill in id if necessary.
         if !html::has_attr(dfn_el, "id") {
-            let dfn_text = html::get_text_content(dfn_el);
-            let name = config::generate_name(&dfn_text);
+            let mut dfn_text = match html::get_attr(dfn_el, "data-lt") {
+                Some(lt) => lt.split('|').next().unwrap().to_owned(),
+                None => html::get_text_content(dfn_el),
+            };
 
-            let id = if let Some(dfn_class) = DFN_TYPE_TO_CLASS.get(dfn_type.as_str()) {
-                format!("{}-{}", dfn_class, name)
-            } else {
-                name
+            if FUNCTIONISH_TYPES.contains(dfn_type.as_str()) {
+                if let Some(index) = dfn_text.find('(') {
+                    dfn_text.truncate(index);
+                }
+            }
+
+            let dfn_for = dfn_for.map(|dfn_for| config::split_for_vals(&dfn_for)[0].to_owned());
+
+            let id = match (dfn_type.as_str(), dfn_for) {
+                ("dfn", _) | ("interface", _) => config::generate_name(&dfn_text),
+                (dfn_type, Some(dfn_for)) if IDL_MEMBER_TYPES.contains(dfn_type) => format!(
+                    "dom-{}",
+                    config::generate_name(&format!("{}-{}", dfn_for, dfn_text))
+                ),
+                (dfn_type, dfn_for) => {
+                    let dfn_class = DFN_TYPE_TO_CLASS[dfn_type];
+
+                    match dfn_for {
+                        Some(dfn_for) => format!(
+                            "{}-{}-{}",
+                            dfn_class,
+                            config::generate_name(&dfn_for),
+                            config::generate_name(&dfn_text)
+                        ),
+                        None => format!("{}-{}", dfn_class, config::generate_name(&dfn_text)),
+                    }
+                }
             };
 
             html::insert_attr(dfn_el, "id", id);
         }
+
+        classified_dfn_els.push(dfn_el.to_owned());
     }
+
+    classified_dfn_els
 }
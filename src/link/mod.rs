@@ -4,17 +4,30 @@ pub mod reference;
 
 use kuchiki::NodeRef;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::config::{self, DFN_SELECTOR};
+use crate::config::{self, DFN_SELECTOR, IDL_TYPES};
+use crate::datadir::DataCache;
 use crate::html::{self, Attr};
 use crate::spec::Spec;
+use biblio::BiblioGroup;
 use reference::query::Query;
+use reference::ReferenceGroup;
+
+// Spec data loaded so far, which can be shared between specs.
+#[derive(Debug, Clone, Default)]
+pub struct SpecDataCache {
+    pub anchors: Arc<DataCache<ReferenceGroup>>,
+    pub biblio: Arc<DataCache<BiblioGroup>>,
+}
 
 pub fn process_biblio_links(doc: &mut Spec) {
     for biblio_link_el in html::select(doc.dom(), "a[data-link-type='biblio']") {
         let biblio_type = html::get_attr(&biblio_link_el, "data-biblio-type").unwrap();
 
-        let mut link_text = html::get_text_content(&biblio_link_el);
+        // The term is kept apart when the link has its own text.
+        let mut link_text = html::get_attr(&biblio_link_el, "data-lt")
+            .unwrap_or_else(|| html::get_text_content(&biblio_link_el));
 
         if !link_text.is_empty()
             && &link_text[0..=0] == "["
@@ -23,7 +36,13 @@ pub fn process_biblio_links(doc: &mut Spec) {
             link_text = link_text[1..link_text.len() - 1].to_owned();
         }
 
-        let mut biblio = doc.biblio_manager.get_biblio(&link_text);
+        let mut biblio = match doc.biblio_manager.get_biblio(&link_text) {
+            Ok(biblio) => biblio,
+            Err(message) => {
+                doc.diagnostics.error(message, None);
+                None
+            }
+        };
 
         if let Some(ref mut biblio) = biblio {
             let name = config::generate_name(&link_text);
@@ -34,7 +53,11 @@ pub fn process_biblio_links(doc: &mut Spec) {
             let storage = match biblio_type.as_str() {
                 "normative" => &mut doc.normative_biblios,
                 "informative" => &mut doc.informative_biblios,
-                _ => die!("Unknown biblio type: {}.", biblio_type),
+                _ => {
+                    doc.diagnostics
+                        .error(format!("Unknown biblio type: {}.", biblio_type), None);
+                    continue;
+                }
             };
 
             storage.insert(biblio.link_text.to_owned(), biblio.to_owned());
@@ -47,20 +70,28 @@ pub fn process_auto_links(doc: &mut Spec) {
         let link_type = determine_link_type(&auto_link_el);
         html::insert_attr(&auto_link_el, "data-link-type", &link_type);
 
+        if IDL_TYPES.contains(link_type.as_str()) {
+            html::add_class(&auto_link_el, "idl-code");
+        }
+
         let link_text = html::get_text_content(&auto_link_el);
 
-        let link_fors = match html::get_attr(&auto_link_el, "data-link-for") {
-            Some(link_for) => Some(config::split_for_vals(&link_for)),
-            None => None,
-        };
+        let link_fors = html::get_attr(&auto_link_el, "data-link-for")
+            .map(|link_for| config::split_for_vals(&link_for));
 
-        let reference = doc.reference_manager.get_reference(Query {
+        let reference = match doc.reference_manager.get_reference(Query {
             link_type: &link_type,
             link_text: &link_text,
             status: None,
             link_fors: &link_fors,
             explicit_for: doc.md.assume_explicit_for,
-        });
+        }) {
+            Ok(reference) => reference,
+            Err(message) => {
+                doc.diagnostics.error(message, None);
+                continue;
+            }
+        };
 
         if let Some(ref reference_spec) = reference.spec {
             if let Some(ref doc_spec) = doc.reference_manager.spec {
@@ -72,21 +103,72 @@ pub fn process_auto_links(doc: &mut Spec) {
                 }
             }
 
-            if let Some(biblio) = doc.biblio_manager.get_biblio(&reference_spec) {
-                doc.normative_biblios
-                    .insert(biblio.link_text.to_owned(), biblio);
+            match doc.biblio_manager.get_biblio(reference_spec) {
+                Ok(Some(biblio)) => {
+                    doc.normative_biblios
+                        .insert(biblio.link_text.to_owned(), biblio);
+                }
+                Ok(None) => {}
+                // The link itself is fine, only the references section is incomplete.
+                Err(message) => doc.diagnostics.warning(message, None),
             }
         }
 
         // Decorate auto-link.
         html::insert_attr(&auto_link_el, "href", &reference.url);
-        let name = reference.url.rsplitn(2, '#').next().unwrap();
+        let name = reference.url.rsplit('#').next().unwrap();
         html::insert_attr(&auto_link_el, "id", format!("ref-for-{}", name));
     }
 
     html::dedup_ids(doc.dom());
 }
 
+// Fill in the text of section links once the headings are numbered.
+pub fn process_section_links(doc: &mut Spec) {
+    let section_link_els = html::select(doc.dom(), "a[data-section]").collect::<Vec<NodeRef>>();
+
+    for section_link_el in section_link_els {
+        html::remove_attr(&section_link_el, "data-section");
+
+        let href = html::get_attr(&section_link_el, "href").unwrap_or_default();
+        let section = match href.strip_prefix('#') {
+            Some(section) => section,
+            None => continue,
+        };
+
+        let heading_el = match html::select_first(doc.dom(), &format!("[id='{}']", section)) {
+            Some(heading_el) => heading_el,
+            None => {
+                doc.diagnostics.error(
+                    format!("Couldn't find target document section {}.", href),
+                    None,
+                );
+                continue;
+            }
+        };
+
+        if !section_link_el.text_contents().trim().is_empty() {
+            continue;
+        }
+
+        let content = match html::select_first(&heading_el, ".content") {
+            Some(content_el) => content_el.text_contents(),
+            None => heading_el.text_contents(),
+        };
+        let content = content.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+        let link_text = match html::get_attr(&heading_el, "data-level") {
+            Some(level) => format!("§\u{202f}{} {}", level, content),
+            None => format!("§\u{202f}{}", content),
+        };
+
+        for child in section_link_el.children() {
+            child.detach();
+        }
+        section_link_el.append(html::new_text(link_text));
+    }
+}
+
 fn determine_link_type(link_el: &NodeRef) -> String {
     match html::get_attr(link_el, "data-link-type") {
         Some(link_type) => link_type,
@@ -180,7 +262,11 @@ fn add_dfn_panels(doc: &mut Spec, dfn_els: &[NodeRef]) {
 
         at_least_one_panel = true;
 
-        html::add_class(dfn_el, "dfn-paneled");
+        // The panel class comes before the classes the dfn already has.
+        match html::get_attr(dfn_el, "class") {
+            Some(class) => html::insert_attr(dfn_el, "class", format!("dfn-paneled {}", class)),
+            None => html::insert_attr(dfn_el, "class", "dfn-paneled"),
+        }
 
         let aside_el = html::new_element(
             "aside",
@@ -213,11 +299,11 @@ fn add_dfn_panels(doc: &mut Spec, dfn_els: &[NodeRef]) {
             let li_el = html::new_element("li", None::<Attr>);
 
             for (i, section_el) in section_els.iter().enumerate() {
-                let section_el_id = match html::get_attr(&section_el, "id") {
+                let section_el_id = match html::get_attr(section_el, "id") {
                     Some(section_el_id) => section_el_id,
                     None => {
                         let id = format!("ref-for-{}", id);
-                        html::insert_attr(&section_el, "id", &id);
+                        html::insert_attr(section_el, "id", &id);
                         id
                     }
                 };
@@ -1,18 +1,37 @@
 use kuchiki::NodeRef;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use super::query::Query;
-use super::source::{QueryMode, ReferenceSource, SourceKind};
+use super::source::{QueryError, QueryMode, ReferenceSource, SourceKind};
+use super::util::spec_matches;
 use super::Reference;
 use crate::config;
 use crate::html;
+use crate::link::SpecDataCache;
 use crate::metadata::Metadata;
 
+// The spec (and for values) to pick a link text's references from when it is ambiguous.
+#[derive(Debug, Clone, Default)]
+pub struct LinkDefault {
+    pub link_type: String,
+    pub spec: String,
+    pub link_fors: Vec<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct ReferenceManager {
     pub local_reference_source: ReferenceSource,
     pub anchor_block_reference_source: ReferenceSource,
     pub external_reference_source: ReferenceSource,
     pub spec: Option<String>,
+    // link text => link defaults
+    link_defaults: HashMap<String, Vec<LinkDefault>>,
+    // specs whose references are never used
+    ignored_specs: HashSet<String>,
+    // spec => the spec whose references are used instead, if it has any
+    replaced_specs: HashMap<String, String>,
 }
 
 impl ReferenceManager {
@@ -26,10 +45,94 @@ impl ReferenceManager {
     }
 
     pub fn set_data(&mut self, md: &Metadata) {
-        self.spec = Some(md.vshortname());
+        self.spec = md.vshortname();
+    }
+
+    pub fn set_spec_data(&mut self, spec_data_dirs: &[PathBuf], cache: &SpecDataCache) {
+        self.external_reference_source
+            .set_spec_data(spec_data_dirs, Arc::clone(&cache.anchors));
+    }
+
+    pub fn add_link_default(&mut self, link_text: String, link_default: LinkDefault) {
+        self.link_defaults
+            .entry(link_text)
+            .or_default()
+            .push(link_default);
+    }
+
+    pub fn add_ignored_spec(&mut self, spec: String, replaced_by: Option<String>) {
+        match replaced_by {
+            Some(replaced_by) => {
+                self.replaced_specs.insert(spec, replaced_by);
+            }
+            None => {
+                self.ignored_specs.insert(spec);
+            }
+        }
+    }
+
+    // Narrow the references down with the link defaults and the ignored specs.
+    fn prefer_references(
+        &self,
+        link_type: &str,
+        link_text: &str,
+        mut references: Vec<Reference>,
+    ) -> Vec<Reference> {
+        let is_from = |reference: &Reference, spec: &str| {
+            reference
+                .spec
+                .as_ref()
+                .is_some_and(|reference_spec| spec_matches(reference_spec, spec))
+        };
+
+        if let Some(link_defaults) = self.link_defaults.get(link_text) {
+            for link_default in link_defaults
+                .iter()
+                .filter(|link_default| link_default.link_type == link_type)
+            {
+                let preferred = references
+                    .iter()
+                    .filter(|reference| {
+                        is_from(reference, &link_default.spec)
+                            && (link_default.link_fors.is_empty()
+                                || link_default
+                                    .link_fors
+                                    .iter()
+                                    .any(|link_for| reference.link_fors.contains(link_for)))
+                    })
+                    .cloned()
+                    .collect::<Vec<Reference>>();
+
+                if !preferred.is_empty() {
+                    return preferred;
+                }
+            }
+        }
+
+        references.retain(|reference| {
+            !self
+                .ignored_specs
+                .iter()
+                .any(|spec| is_from(reference, spec))
+        });
+
+        // A replaced spec is only dropped when its replacement defines the term too.
+        let replacements = self
+            .replaced_specs
+            .iter()
+            .filter(|(_, replaced_by)| {
+                references
+                    .iter()
+                    .any(|reference| is_from(reference, replaced_by))
+            })
+            .map(|(spec, _)| spec.to_owned())
+            .collect::<Vec<String>>();
+        references.retain(|reference| !replacements.iter().any(|spec| is_from(reference, spec)));
+
+        references
     }
 
-    pub fn get_reference(&mut self, query: Query) -> Reference {
+    pub fn get_reference(&mut self, query: Query) -> Result<Reference, String> {
         let link_type = query.link_type;
         let link_text = query.link_text;
         let link_fors = query.link_fors;
@@ -45,7 +148,7 @@ impl ReferenceManager {
             },
             QueryMode::Inexact,
         ) {
-            return local_references[0].to_owned();
+            return Ok(local_references[0].to_owned());
         }
 
         // Load anchor block references.
@@ -59,33 +162,61 @@ impl ReferenceManager {
             },
             QueryMode::Inexact,
         ) {
-            return anchor_block_references[0].to_owned();
+            let anchor_block_references =
+                self.prefer_references(link_type, link_text, anchor_block_references);
+
+            if let Some(reference) = anchor_block_references.into_iter().next() {
+                return Ok(reference);
+            }
         }
 
         // Load external references.
-        let external_references = self
-            .external_reference_source
-            .query_references(
-                Query {
-                    link_type,
-                    link_text,
-                    status: Some("current"),
-                    link_fors,
-                    explicit_for: query.explicit_for,
-                },
-                QueryMode::Exact,
-            )
-            .unwrap();
-
-        external_references[0].to_owned()
+        match self.external_reference_source.query_references(
+            Query {
+                link_type,
+                link_text,
+                status: Some("current"),
+                link_fors,
+                explicit_for: query.explicit_for,
+            },
+            QueryMode::Exact,
+        ) {
+            Ok(external_references) => self
+                .prefer_references(link_type, link_text, external_references)
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    format!(
+                        "No '{}' refs found for '{}' outside the ignored specs.",
+                        link_type, link_text
+                    )
+                }),
+            Err(QueryError::Data(message)) => Err(message),
+            Err(QueryError::Status) => Err(format!(
+                "No '{}' refs found for '{}' with status 'current'.",
+                link_type, link_text
+            )),
+            Err(QueryError::For) => Err(format!(
+                "No '{}' refs found for '{}' with for='{}'.",
+                link_type,
+                link_text,
+                link_fors
+                    .as_ref()
+                    .map_or_else(|| "/".to_owned(), |link_fors| link_fors.join(", "))
+            )),
+            Err(_) => Err(format!(
+                "No '{}' refs found for '{}'.",
+                link_type, link_text
+            )),
+        }
     }
 
     pub fn add_local_dfns(&mut self, dfn_els: &[NodeRef]) {
         for dfn_el in dfn_els {
-            let link_text = html::get_text_content(&dfn_el);
-            let link_type = html::closest_attr_in(&dfn_el, "data-dfn-type").unwrap();
+            let link_text = html::get_text_content(dfn_el);
+            let link_type = html::closest_attr_in(dfn_el, "data-dfn-type").unwrap();
 
-            let link_fors = match html::closest_attr_in(&dfn_el, "data-dfn-for") {
+            let link_fors = match html::closest_attr_in(dfn_el, "data-dfn-for") {
                 Some(dfn_for) => config::split_for_vals(&dfn_for),
                 None => Vec::new(),
             };
@@ -94,7 +225,7 @@ impl ReferenceManager {
                 link_type,
                 spec: self.spec.to_owned(),
                 status: "local".to_owned(),
-                url: format!("#{}", html::get_attr(&dfn_el, "id").unwrap()),
+                url: format!("#{}", html::get_attr(dfn_el, "id").unwrap()),
                 link_fors,
             };
 
@@ -103,3 +234,61 @@ impl ReferenceManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LinkDefault, ReferenceManager};
+    use crate::link::reference::query::Query;
+    use crate::link::reference::Reference;
+
+    fn get_url(manager: &mut ReferenceManager) -> Result<String, String> {
+        manager
+            .get_reference(Query {
+                link_type: "dfn",
+                link_text: "term",
+                status: None,
+                link_fors: &None,
+                explicit_for: false,
+            })
+            .map(|reference| reference.url)
+    }
+
+    #[test]
+    fn test_prefer_references() {
+        let mut manager = ReferenceManager::new();
+        for spec in ["foo-1", "bar", "baz-2"].iter() {
+            manager.anchor_block_reference_source.add_reference(
+                "term".to_owned(),
+                Reference {
+                    link_type: "dfn".to_owned(),
+                    spec: Some((*spec).to_owned()),
+                    status: "anchor-block".to_owned(),
+                    url: format!("https://{}.test/#term", spec),
+                    link_fors: Vec::new(),
+                },
+            );
+        }
+        assert_eq!(get_url(&mut manager).unwrap(), "https://foo-1.test/#term");
+
+        manager.add_ignored_spec("foo".to_owned(), None);
+        assert_eq!(get_url(&mut manager).unwrap(), "https://bar.test/#term");
+
+        // The replacement doesn't define the term, so the replaced spec is kept.
+        manager.add_ignored_spec("bar".to_owned(), Some("qux".to_owned()));
+        assert_eq!(get_url(&mut manager).unwrap(), "https://bar.test/#term");
+
+        manager.add_ignored_spec("bar".to_owned(), Some("baz".to_owned()));
+        assert_eq!(get_url(&mut manager).unwrap(), "https://baz-2.test/#term");
+
+        // Link defaults win over the ignored specs.
+        manager.add_link_default(
+            "term".to_owned(),
+            LinkDefault {
+                link_type: "dfn".to_owned(),
+                spec: "foo".to_owned(),
+                link_fors: Vec::new(),
+            },
+        );
+        assert_eq!(get_url(&mut manager).unwrap(), "https://foo-1.test/#term");
+    }
+}
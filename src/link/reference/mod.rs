@@ -3,6 +3,11 @@ pub mod query;
 mod source;
 mod util;
 
+use std::collections::HashMap;
+
+// text => references
+pub type ReferenceGroup = HashMap<String, Vec<Reference>>;
+
 #[derive(Debug, Clone)]
 pub struct Reference {
     pub link_type: String,
@@ -1,10 +1,13 @@
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use super::query::Query;
 use super::util;
-use super::Reference;
+use super::{Reference, ReferenceGroup};
 use crate::config;
+use crate::datadir::{self, DataCache, DataFile};
 use crate::util::reader;
 
 #[derive(Debug, PartialEq)]
@@ -14,6 +17,7 @@ pub enum SourceKind {
     External,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for SourceKind {
     fn default() -> Self {
         SourceKind::Local
@@ -23,7 +27,11 @@ impl Default for SourceKind {
 #[derive(Debug, Default)]
 pub struct ReferenceSource {
     source_kind: SourceKind,
-    loaded_groups: HashSet<String>,
+    spec_data_dirs: Vec<PathBuf>,
+    spec_data_cache: Arc<DataCache<ReferenceGroup>>,
+    // group => references loaded from the spec data
+    loaded_groups: HashMap<String, Arc<ReferenceGroup>>,
+    loaded_files: Vec<PathBuf>,
     // text => references
     references: HashMap<String, Vec<Reference>>,
 }
@@ -40,6 +48,7 @@ pub enum QueryError {
     LinkType,
     Status,
     For,
+    Data(String),
 }
 
 impl ReferenceSource {
@@ -71,12 +80,12 @@ impl ReferenceSource {
     ) -> Result<Vec<Reference>, QueryError> {
         // Filter references by link text.
         let mut references = match query_mode {
-            QueryMode::Exact => self.fetch_references(query.link_text),
+            QueryMode::Exact => self.fetch_references(query.link_text)?,
             QueryMode::Inexact => {
                 let mut references = Vec::new();
 
                 for text in util::link_text_variations(query.link_type, query.link_text) {
-                    references.extend(self.fetch_references(&text));
+                    references.extend(self.fetch_references(&text)?);
                 }
 
                 references
@@ -88,21 +97,24 @@ impl ReferenceSource {
         }
 
         // Filter references by link type.
-        references = references
-            .into_iter()
-            .filter(|reference| reference.link_type == query.link_type)
-            .collect();
+        references.retain(|reference| reference.link_type == query.link_type);
 
         if references.is_empty() {
             return Err(QueryError::LinkType);
         }
 
-        // Filter references by status.
+        // Filter references by status, letting specs without a reference of that status
+        // fall back to their other ones.
         if let Some(status) = query.status {
-            references = references
-                .into_iter()
+            let specs_with_status = references
+                .iter()
                 .filter(|reference| reference.status == status)
-                .collect();
+                .map(|reference| reference.spec.to_owned())
+                .collect::<Vec<Option<String>>>();
+
+            references.retain(|reference| {
+                reference.status == status || !specs_with_status.contains(&reference.spec)
+            });
 
             if references.is_empty() {
                 return Err(QueryError::Status);
@@ -122,19 +134,13 @@ impl ReferenceSource {
 
         match query.link_fors {
             Some(link_fors) => {
-                references = references
-                    .into_iter()
-                    .filter(|reference| match_link_fors(&reference.link_fors, link_fors))
-                    .collect();
+                references.retain(|reference| match_link_fors(&reference.link_fors, link_fors));
             }
             None => {
                 if query.explicit_for {
-                    references = references
-                        .into_iter()
-                        .filter(|reference| {
-                            match_link_fors(&reference.link_fors, &["/".to_owned()])
-                        })
-                        .collect();
+                    references.retain(|reference| {
+                        match_link_fors(&reference.link_fors, &["/".to_owned()])
+                    });
                 }
             }
         };
@@ -146,6 +152,20 @@ impl ReferenceSource {
         Ok(references)
     }
 
+    pub fn set_spec_data(
+        &mut self,
+        spec_data_dirs: &[PathBuf],
+        spec_data_cache: Arc<DataCache<ReferenceGroup>>,
+    ) {
+        self.spec_data_dirs = spec_data_dirs.to_vec();
+        self.spec_data_cache = spec_data_cache;
+    }
+
+    // The data files read so far.
+    pub fn loaded_files(&self) -> &[PathBuf] {
+        &self.loaded_files
+    }
+
     pub fn add_reference(&mut self, link_text: String, reference: Reference) {
         self.references
             .entry(link_text)
@@ -153,58 +173,79 @@ impl ReferenceSource {
             .push(reference);
     }
 
-    fn fetch_references(&mut self, link_text: &str) -> Vec<Reference> {
+    fn fetch_references(&mut self, link_text: &str) -> Result<Vec<Reference>, QueryError> {
         if let Some(references) = self.references.get(link_text) {
-            return references.to_owned();
+            return Ok(references.to_owned());
         }
 
         if self.source_kind != SourceKind::External {
-            return Vec::new();
+            return Ok(Vec::new());
         }
 
         let group = config::generate_group_name(link_text);
 
-        if self.loaded_groups.contains(&group) {
-            return Vec::new();
+        if !self.loaded_groups.contains_key(&group) {
+            // Mark the group as loaded even if it fails, so that the failure is only reported once.
+            let loaded = self.load_spec_data(&group);
+            self.loaded_groups
+                .insert(group.clone(), loaded.clone().unwrap_or_default());
+            loaded.map_err(QueryError::Data)?;
         }
 
-        self.load_spec_data(&group);
-        self.loaded_groups.insert(group);
-
-        match self.references.get(link_text) {
-            Some(references) => references.to_owned(),
-            None => Vec::new(),
+        match self.loaded_groups[&group].get(link_text) {
+            Some(references) => Ok(references.to_owned()),
+            None => Ok(Vec::new()),
         }
     }
 
-    fn load_spec_data(&mut self, group: &str) {
-        let data_path = Path::new("spec-data")
-            .join("anchors")
-            .join(format!("anchors-{}.data", group));
+    fn load_spec_data(&mut self, group: &str) -> Result<Arc<ReferenceGroup>, String> {
+        let rel_path = Path::new("anchors").join(format!("anchors-{}.data", group));
 
-        let mut lines = match reader::read_lines(&data_path) {
-            Ok(lines) => lines,
-            _ => die!(
-                "Fail to load reference data file: \"{}\".",
-                data_path.to_str().unwrap()
-            ),
+        let data_file = match datadir::find_spec_data(&self.spec_data_dirs, &rel_path) {
+            Some(data_file) => data_file,
+            None => {
+                return Err(format!(
+                    "Fail to load reference data file: \"{}\".",
+                    rel_path.display()
+                ))
+            }
         };
+        self.loaded_files
+            .extend(data_file.path().map(Path::to_path_buf));
 
+        self.spec_data_cache
+            .get_or_load(&data_file, || parse_spec_data(&data_file, &rel_path))
+    }
+}
+
+fn parse_spec_data(data_file: &DataFile, rel_path: &Path) -> Result<ReferenceGroup, String> {
+    let mut lines = match data_file.open() {
+        Ok(data) => data.lines(),
+        Err(_) => {
+            return Err(format!(
+                "Fail to load reference data file: \"{}\".",
+                rel_path.display()
+            ))
+        }
+    };
+    let mut references = ReferenceGroup::new();
+
+    let mut parse = || -> io::Result<()> {
         while let Some(key) = lines.next() {
-            let key = key.unwrap();
-            let link_type = lines.next().unwrap().unwrap();
-            let spec = lines.next().unwrap().unwrap();
-            lines.next(); // shortname
-            lines.next(); // level
-            let status = lines.next().unwrap().unwrap();
-            let url = lines.next().unwrap().unwrap();
-            lines.next(); // export
-            lines.next(); // normative
+            let key = key?;
+            let link_type = reader::next_line(&mut lines)?;
+            let spec = reader::next_line(&mut lines)?;
+            reader::next_line(&mut lines)?; // shortname
+            reader::next_line(&mut lines)?; // level
+            let status = reader::next_line(&mut lines)?;
+            let url = reader::next_line(&mut lines)?;
+            reader::next_line(&mut lines)?; // export
+            reader::next_line(&mut lines)?; // normative
 
             let mut link_fors = Vec::new();
 
             loop {
-                let line = lines.next().unwrap().unwrap();
+                let line = reader::next_line(&mut lines)?;
 
                 if line == "-" {
                     break;
@@ -221,7 +262,19 @@ impl ReferenceSource {
                 link_fors,
             };
 
-            self.add_reference(key, reference);
+            references.entry(key).or_default().push(reference);
         }
-    }
+
+        Ok(())
+    };
+
+    parse().map_err(|err| {
+        format!(
+            "Fail to parse reference data file: \"{}\" ({}).",
+            rel_path.display(),
+            err
+        )
+    })?;
+
+    Ok(references)
 }
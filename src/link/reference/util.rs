@@ -113,9 +113,38 @@ pub fn link_text_variations(link_type: &str, link_text: &str) -> HashSet<String>
     vars
 }
 
+// Whether the spec of a reference (e.g. "css-values-4") is the given spec,
+// which may leave out the level (e.g. "css-values").
+pub fn spec_matches(reference_spec: &str, spec: &str) -> bool {
+    let reference_spec = reference_spec.to_lowercase();
+    let spec = spec.to_lowercase();
+
+    if reference_spec == spec {
+        return true;
+    }
+
+    match reference_spec.strip_prefix(&spec) {
+        Some(level) => {
+            level.len() > 1
+                && level.starts_with('-')
+                && level[1..].chars().all(|ch| ch.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::link_text_variations;
+    use super::{link_text_variations, spec_matches};
+
+    #[test]
+    fn test_spec_matches() {
+        assert!(spec_matches("html", "HTML"));
+        assert!(spec_matches("css-values-4", "css-values"));
+        assert!(spec_matches("css-values-4", "css-values-4"));
+        assert!(!spec_matches("css-values-4", "css"));
+        assert!(!spec_matches("css-values", "css-values-4"));
+    }
 
     #[test]
     fn test_link_text_variations() {
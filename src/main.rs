@@ -1,28 +1,5 @@
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate markup5ever;
-#[macro_use]
-extern crate maplit;
-
-#[macro_use]
-mod util;
-mod boilerplate;
-mod clean;
 mod client;
-mod config;
-mod datablock;
-mod fix;
-mod heading;
-mod html;
-mod line;
-mod link;
-mod markdown;
-mod metadata;
-mod shorthand;
-mod spec;
-#[cfg(test)]
-mod test;
+mod server;
 
 fn main() {
     client::run();
@@ -5,12 +5,13 @@ use regex::Regex;
 
 use crate::config::INLINE_ELEMENT_TAGS;
 use crate::util;
+use crate::util::message::Diagnostics;
 use token::*;
 
 // Get HTML lines.
-pub fn parse(lines: &[String], tab_size: u32) -> Vec<String> {
-    let tokens = tokenize_lines(lines, tab_size);
-    parse_tokens(&tokens, tab_size)
+pub fn parse(lines: &[String], tab_size: u32, diagnostics: &mut Diagnostics) -> Vec<String> {
+    let tokens = tokenize_lines(lines, tab_size, diagnostics);
+    parse_tokens(&tokens, tab_size, diagnostics)
 }
 
 lazy_static! {
@@ -115,7 +116,8 @@ fn starts_with_inline_element(line: &str) -> bool {
 }
 
 // Turn lines of text into block tokens, which'll be turned into MD blocks later.
-fn tokenize_lines(lines: &[String], tab_size: u32) -> Vec<Token> {
+#[allow(clippy::needless_borrow)]
+fn tokenize_lines(lines: &[String], tab_size: u32, diagnostics: &mut Diagnostics) -> Vec<Token> {
     let make_token = |kind: TokenKind, line: &str| -> Token {
         match kind {
             TokenKind::Blank => Token::new_blank(),
@@ -173,7 +175,13 @@ fn tokenize_lines(lines: &[String], tab_size: u32) -> Vec<Token> {
                 let is_nestable = match element_tag {
                     "pre" => true,
                     "xmp" | "script" | "style" => false,
-                    _ => die!("Unknown opaque element tag: {}.", element_tag),
+                    _ => {
+                        diagnostics.error(
+                            format!("[Markdown] Unknown opaque element tag: {}.", element_tag),
+                            None,
+                        );
+                        false
+                    }
                 };
 
                 raw_token_stack.push(RawToken {
@@ -253,7 +261,7 @@ fn tokenize_lines(lines: &[String], tab_size: u32) -> Vec<Token> {
     tokens
 }
 
-fn parse_tokens(tokens: &[Token], tab_size: u32) -> Vec<String> {
+fn parse_tokens(tokens: &[Token], tab_size: u32, diagnostics: &mut Diagnostics) -> Vec<String> {
     let mut stream = TokenStream::new(tokens, tab_size);
     let mut lines = Vec::new();
 
@@ -270,7 +278,13 @@ fn parse_tokens(tokens: &[Token], tab_size: u32) -> Vec<String> {
                 if stream.next().kind == TokenKind::EqualsLine
                     || stream.next().kind == TokenKind::DashLine
                 {
-                    lines.push(parse_multi_line_heading(&mut stream));
+                    match parse_multi_line_heading(&mut stream) {
+                        Ok(heading) => lines.push(heading),
+                        Err(message) => {
+                            diagnostics.error(message, None);
+                            lines.push(stream.curr().line.clone());
+                        }
+                    }
                 } else if stream.prev().kind == TokenKind::Blank {
                     lines.extend(parse_paragraph(&mut stream));
                 } else {
@@ -281,10 +295,16 @@ fn parse_tokens(tokens: &[Token], tab_size: u32) -> Vec<String> {
                 lines.push(make_horizontal_rule());
             }
             TokenKind::Numbered | TokenKind::Bulleted | TokenKind::Dt | TokenKind::Dd => {
-                lines.extend(parse_list(&mut stream));
+                match parse_list(&mut stream, diagnostics) {
+                    Ok(list_lines) => lines.extend(list_lines),
+                    Err(message) => {
+                        diagnostics.error(message, None);
+                        lines.push(stream.curr().line.clone());
+                    }
+                }
             }
             TokenKind::QuoteBlock => {
-                lines.extend(parse_quote_block(&mut stream));
+                lines.extend(parse_quote_block(&mut stream, diagnostics));
             }
             _ => {
                 lines.push(stream.curr().line.clone());
@@ -327,7 +347,7 @@ fn parse_single_line_heading(stream: &mut TokenStream) -> String {
     )
 }
 
-fn parse_multi_line_heading(stream: &mut TokenStream) -> String {
+fn parse_multi_line_heading(stream: &mut TokenStream) -> Result<String, String> {
     lazy_static! {
         // regex for text with id
         static ref TEXT_WITH_ID_REG: Regex = Regex::new(
@@ -345,11 +365,13 @@ fn parse_multi_line_heading(stream: &mut TokenStream) -> String {
     let level = match stream.next().kind {
         TokenKind::EqualsLine => 2,
         TokenKind::DashLine => 3,
-        _ => die!(
-            "[Markdown] Fail to parse a multi-line heading from:\n{}\n{}",
-            stream.curr().line,
-            stream.next().line
-        ),
+        _ => {
+            return Err(format!(
+                "[Markdown] Fail to parse a multi-line heading from:\n{}\n{}",
+                stream.curr().line,
+                stream.next().line
+            ))
+        }
     };
 
     let (text, id_attr) = if let Some(caps) = TEXT_WITH_ID_REG.captures(&stream.curr().line) {
@@ -370,7 +392,7 @@ fn parse_multi_line_heading(stream: &mut TokenStream) -> String {
 
     stream.advance();
 
-    heading
+    Ok(heading)
 }
 
 fn parse_paragraph(stream: &mut TokenStream) -> Vec<String> {
@@ -392,12 +414,15 @@ fn parse_paragraph(stream: &mut TokenStream) -> Vec<String> {
     lines
 }
 
-fn parse_list(stream: &mut TokenStream) -> Vec<String> {
+fn parse_list(
+    stream: &mut TokenStream,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<String>, String> {
     let (target_tokens, reg, outer_tag): (Vec<TokenKind>, &Regex, &str) = match stream.curr().kind {
         TokenKind::Numbered => (vec![TokenKind::Numbered], &NUMBERED_REG, "ol"),
         TokenKind::Bulleted => (vec![TokenKind::Bulleted], &BULLETED_REG, "ul"),
         TokenKind::Dt | TokenKind::Dd => (vec![TokenKind::Dt, TokenKind::Dd], &DEF_REG, "dl"),
-        _ => die!("[Markdown] Try to parse a line that isn't a list."),
+        _ => return Err("[Markdown] Try to parse a line that isn't a list.".to_owned()),
     };
 
     let outer_el_attr = match stream.curr().kind {
@@ -448,11 +473,12 @@ fn parse_list(stream: &mut TokenStream) -> Vec<String> {
 
             stream.advance();
 
-            lines.push(util::indent::trim_indent(
-                &stream.curr().line,
-                top_indent_level + 1,
-                stream.tab_size(),
-            ));
+            // Lines that aren't indented enough are taken as they are.
+            let line = &stream.curr().line;
+            lines.push(
+                util::indent::trim_indent(line, top_indent_level + 1, stream.tab_size())
+                    .unwrap_or_else(|_| line.trim_start().to_owned()),
+            );
         }
 
         (token_kind, lines)
@@ -470,7 +496,7 @@ fn parse_list(stream: &mut TokenStream) -> Vec<String> {
 
         // Generate an item.
         lines.push(format!("<{} data-md>", tag));
-        lines.extend(parse(&item_lines, stream.tab_size()));
+        lines.extend(parse(&item_lines, stream.tab_size(), diagnostics));
         lines.push(format!("</{}>", tag));
 
         // Break the loop if we reach the end of this list.
@@ -496,18 +522,14 @@ fn parse_list(stream: &mut TokenStream) -> Vec<String> {
 
     lines.push(format!("</{}>", outer_tag));
 
-    lines
+    Ok(lines)
 }
 
-fn parse_quote_block(stream: &mut TokenStream) -> Vec<String> {
+fn parse_quote_block(stream: &mut TokenStream, diagnostics: &mut Diagnostics) -> Vec<String> {
     let extract_text_from_quote_block = |line: &str| -> Option<String> {
         let caps = QUOTE_BLOCK_REG.captures(line).unwrap();
 
-        if let Some(text) = caps.name("text") {
-            Some(text.as_str().to_owned())
-        } else {
-            None
-        }
+        caps.name("text").map(|text| text.as_str().to_owned())
     };
 
     let mut inner_lines = Vec::new();
@@ -541,7 +563,7 @@ fn parse_quote_block(stream: &mut TokenStream) -> Vec<String> {
     let mut lines = Vec::new();
 
     lines.push("<blockquote>".to_owned());
-    lines.extend(parse(&inner_lines, stream.tab_size()));
+    lines.extend(parse(&inner_lines, stream.tab_size(), diagnostics));
     lines.push("</blockquote>".to_owned());
 
     lines
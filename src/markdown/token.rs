@@ -46,16 +46,19 @@ impl Token {
     }
 
     #[inline]
+    #[allow(clippy::legacy_numeric_constants)]
     pub fn new_blank() -> Self {
         Token::new(TokenKind::Blank, "", u32::max_value())
     }
 
     #[inline]
+    #[allow(clippy::legacy_numeric_constants)]
     pub fn new_end() -> Self {
         Token::new(TokenKind::End, "", u32::max_value())
     }
 
     #[inline]
+    #[allow(clippy::legacy_numeric_constants)]
     pub fn new_raw<T: Into<String>>(line: T) -> Self {
         Token::new(TokenKind::Raw, line, u32::max_value())
     }
@@ -105,6 +108,7 @@ impl<'a> TokenStream<'a> {
     }
 
     #[inline]
+    #[allow(clippy::needless_borrow)]
     pub fn prev(&self) -> &Token {
         if self.curr == 0 {
             &self.before
@@ -114,11 +118,13 @@ impl<'a> TokenStream<'a> {
     }
 
     #[inline]
+    #[allow(clippy::needless_borrow)]
     pub fn next(&self) -> &Token {
         &self.nth(self.curr + 1)
     }
 
     #[inline]
+    #[allow(clippy::needless_borrow)]
     pub fn next_next(&self) -> &Token {
         &self.nth(self.curr + 2)
     }
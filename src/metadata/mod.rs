@@ -1,43 +1,151 @@
 pub mod parse;
+pub mod registry;
 
 use indexmap::map::IndexMap;
 use regex::Regex;
 use serde_json::map::Map;
 use serde_json::{self, Value};
+use std::fmt;
 use titlecase::titlecase;
 
-use self::parse::{Editor, EditorTerm};
-use crate::config::SHORT_TO_LONG_STATUS;
+use self::parse::{Editor, EditorTerm, Expires, SoftBool, Warning};
+use self::registry::Join;
+use crate::config::{
+    self, LEVELLED_GROUPS, MEGA_GROUPS, NO_ED_STATUSES, SHORT_TO_LONG_STATUS, TR_STATUSES,
+    UNLEVELLED_STATUSES,
+};
+use crate::html;
 use crate::line::Line;
 use crate::markdown;
 use crate::spec::Spec;
 use crate::util::boolset::BoolSet;
 use crate::util::date::Date;
+use crate::util::message::{Diagnostics, Severity};
+
+// Where a piece of metadata came from.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum MetadataSource {
+    Default,
+    File(String),
+    #[default]
+    Document,
+    CommandLine,
+}
+
+impl fmt::Display for MetadataSource {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataSource::Default => write!(fmt, "default"),
+            MetadataSource::File(path) => write!(fmt, "file {}", path),
+            MetadataSource::Document => write!(fmt, "document"),
+            MetadataSource::CommandLine => write!(fmt, "command line"),
+        }
+    }
+}
+
+// The raw values of a metadata key, along with the layer that set them last.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataEntry {
+    pub values: Vec<String>,
+    pub source: MetadataSource,
+}
 
 #[derive(Debug, Clone, Default)]
 pub struct Metadata {
     pub has_keys: bool,
+    pub source: MetadataSource,
+    // key => raw values
+    pub entries: IndexMap<String, MetadataEntry>,
     // required metadata
     pub abs: Vec<String>,
     pub ed: Option<String>,
     pub level: Option<String>,
     pub shortname: Option<String>,
     pub raw_status: Option<String>,
+    // the raw status prefixed with its megagroup, e.g. "w3c/WD"
+    pub status: Option<String>,
     // optional metadata
+    pub advisement_class: Option<String>,
+    pub assertion_class: Option<String>,
     pub assume_explicit_for: bool,
+    pub audience: Vec<String>,
+    pub block_elements: Vec<String>,
     pub boilerplate: BoolSet<String>,
+    pub can_i_use_urls: Vec<String>,
     pub canonical_url: Option<String>,
+    pub complain_about: BoolSet<String>,
+    pub custom_warning_text: Vec<String>,
+    pub custom_warning_title: Option<String>,
     pub date: Date,
+    pub deadline: Option<Date>,
+    pub default_biblio_display: Option<String>,
+    pub default_biblio_status: Option<String>,
+    pub default_highlight: Option<String>,
+    pub default_ref_status: Option<String>,
     pub editors: Vec<Editor>,
     pub editor_term: Option<EditorTerm>,
+    pub expires: Option<Expires>,
+    pub external_infotrees: BoolSet<String>,
+    pub favicon: Option<String>,
+    pub force_crossorigin: Option<bool>,
+    pub former_editors: Vec<Editor>,
     pub group: Option<String>,
+    pub h1: Option<String>,
+    pub ignore_can_i_use_url_failures: Vec<String>,
+    pub ignore_mdn_failures: Vec<String>,
+    pub ignored_terms: Vec<String>,
+    pub ignored_vars: Vec<String>,
+    pub implementation_report: Option<String>,
+    pub include_can_i_use_panels: Option<bool>,
+    pub include_mdn_panels: Option<SoftBool>,
     pub indent: Option<u32>,
     pub infer_css_dfns: Option<bool>,
+    pub informative_classes: Vec<String>,
+    pub inline_github_issues: Option<String>,
+    pub issue_class: Option<String>,
+    pub issue_tracker_template: Option<String>,
+    pub issue_tracking: Vec<(String, String)>,
+    pub line_numbers: Option<bool>,
+    pub link_defaults: Vec<String>,
+    pub local_boilerplate: BoolSet<String>,
+    pub logo: Option<String>,
+    pub mailing_list: Option<String>,
+    pub mailing_list_archives: Option<String>,
     pub markup_shorthands: BoolSet<String>,
+    pub max_toc_depth: Option<u32>,
+    pub metadata_include: BoolSet<String>,
+    pub metadata_order: Vec<String>,
+    pub no_abstract: Option<bool>,
+    pub no_editor: Option<bool>,
+    pub note_class: Option<String>,
+    pub opaque_elements: Vec<String>,
+    pub prepare_for_tr: Option<bool>,
+    pub previous_versions: Vec<String>,
     pub remove_multiple_links: Option<bool>,
+    pub repository: Option<String>,
+    pub required_ids: Vec<String>,
+    pub slim_build_artifact: Option<bool>,
+    pub status_text: Vec<String>,
+    pub test_suite: Option<String>,
     pub title: Option<String>,
+    pub text_macros: IndexMap<String, String>,
+    pub toggle_diffs: Option<bool>,
     pub tr: Option<String>,
+    pub tracking_vector_alt_text: Option<String>,
+    pub tracking_vector_class: Option<String>,
+    pub tracking_vector_image: Option<String>,
+    pub tracking_vector_image_height: Option<u32>,
+    pub tracking_vector_image_width: Option<u32>,
+    pub tracking_vector_title: Option<String>,
+    pub translate_ids: Vec<String>,
+    pub translations: Vec<String>,
+    pub use_dfn_panels: Option<bool>,
+    pub use_i_autolinks: Option<bool>,
+    pub version_history: Vec<String>,
+    pub warning: Option<Warning>,
     pub work_status: Option<String>,
+    pub wpt_display: Option<String>,
+    pub wpt_path_prefix: Option<String>,
     // custom metadata
     pub custom_md: IndexMap<String, Vec<String>>,
 }
@@ -46,6 +154,7 @@ impl Metadata {
     pub fn new() -> Self {
         Metadata {
             boilerplate: BoolSet::new_with_default(true),
+            metadata_include: BoolSet::new_with_default(true),
             markup_shorthands: {
                 let mut bs = BoolSet::new_with_default(false);
                 bs.insert("css".to_owned(), true);
@@ -60,214 +169,100 @@ impl Metadata {
         }
     }
 
-    pub fn from_json(data: String) -> Self {
-        let mut md = Metadata::new();
+    pub fn with_source(source: MetadataSource) -> Self {
+        Metadata {
+            source,
+            ..Metadata::new()
+        }
+    }
+
+    pub fn from_json(data: String, source: MetadataSource, diagnostics: &mut Diagnostics) -> Self {
+        let mut md = Metadata::with_source(source);
         let obj: Map<String, Value> = match serde_json::from_str(&data) {
             Ok(Value::Object(obj)) => obj,
-            _ => die!("Fail to load JSON:\n{}", data),
+            _ => {
+                diagnostics.error(format!("Fail to load JSON:\n{}", data), None);
+                return md;
+            }
         };
 
         for (key, val) in obj.iter() {
-            match val {
-                Value::String(str_val) => md.add_data(key, str_val, None),
-                Value::Array(arr_val) => {
-                    for indiv_val in arr_val {
-                        match indiv_val {
-                            Value::String(str_val) => md.add_data(key, str_val, None),
-                            _ => die!(
-                                concat!(
-                                    "JSON metadata values must be strings or arrays of strings. ",
-                                    "\"{0}\" is something else."
-                                ),
-                                key
+            let vals = match val {
+                Value::String(str_val) => vec![str_val.as_str()],
+                Value::Array(arr_val) if arr_val.iter().all(Value::is_string) => {
+                    arr_val.iter().filter_map(Value::as_str).collect()
+                }
+                _ => {
+                    diagnostics.error(
+                        format!(
+                            concat!(
+                                "JSON metadata values must be strings or arrays of strings. ",
+                                "\"{0}\" is something else."
                             ),
-                        }
-                    }
+                            key
+                        ),
+                        None,
+                    );
+                    continue;
+                }
+            };
+
+            for val in vals {
+                if let Err(message) = md.add_data(key, val) {
+                    diagnostics.error(message, None);
                 }
-                _ => die!(
-                    concat!(
-                        "JSON metadata values must be strings or arrays of strings. ",
-                        "\"{0}\" is something else."
-                    ),
-                    key
-                ),
             }
         }
 
         md
     }
 
-    pub fn add_data(&mut self, key: &str, val: &str, line_num: Option<u32>) {
-        let mut key = key.trim().to_string();
-
-        if key != "ED" && key != "TR" && key != "URL" {
-            key = titlecase(&key);
-        }
+    pub fn add_data(&mut self, key: &str, val: &str) -> Result<(), String> {
+        let key = key.trim();
 
         if key.starts_with('!') {
-            let key = &key[1..];
-            self.custom_md
-                .entry(key.to_owned())
-                .or_default()
-                .push(val.to_owned());
-            return;
+            let key = titlecase(key);
+            self.add_custom_data(key.trim_start_matches('!'), val);
+            self.record_entry(&key, val, self.source.clone());
+            return Ok(());
         }
 
-        match key.as_str() {
-            "Abstract" => {
-                let val = val.to_owned();
-                self.abs.push(val);
-            }
-            "Assume Explicit For" => {
-                let val = match parse::parse_bool(val) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        die!("The \"Assume Explicit For\" field must be boolish. Got: {}.", val; line_num)
-                    }
-                };
-                self.assume_explicit_for = val;
-            }
-            "ED" => {
-                let val = val.to_owned();
-                self.ed = Some(val);
-            }
-            "Level" => {
-                let val = parse::parse_level(val);
-                self.level = Some(val);
-            }
-            "Shortname" => {
-                let val = val.to_owned();
-                self.shortname = Some(val);
-            }
-            "Status" => {
-                let val = val.to_owned();
-                self.raw_status = Some(val);
-            }
-            "Boilerplate" => {
-                let val = match parse::parse_boilerplate(val) {
-                    Ok(val) => val,
-                    Err(_) => die!(
-                        concat!(
-                            "Boilerplate metadata pieces are a boilerplate label and a boolean. ",
-                            "Got: {}."
-                        ),
-                        val; line_num
-                    ),
-                };
-                self.boilerplate.update(&val);
-            }
-            "Canonical URL" => {
-                let val = val.to_owned();
-                self.canonical_url = Some(val);
-            }
-            "Date" => {
-                let val = match parse::parse_date(val) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        die!("The \"Date\" field must be in the format YYYY-MM-DD."; line_num)
-                    }
-                };
-                self.date = val;
-            }
-            "Editor" => {
-                let val = match parse::parse_editor(val) {
-                    Ok(val) => val,
-                    Err(_) => die!(
-                        concat!(
-                            "\"Editor\" format is \"<name>, <affiliation>?, <email-or-contact-page>?\". ",
-                            "Got: {}."
-                        ),
-                        val; line_num
-                    ),
-                };
-                self.editors.push(val);
-            }
-            "Editor Term" => {
-                let val = match parse::parse_editor_term(val) {
-                    Ok(val) => val,
-                    Err(_) => die!(
-                        concat!(
-                            "\"Editor Term\" format is \"<singular-term>, <plural-term>\". ",
-                            "Got: {}."
-                        ),
-                        val; line_num
+        let md_key = match registry::find_key(key) {
+            Some(md_key) => md_key,
+            None => {
+                return Err(match registry::suggest_key(key) {
+                    Some(suggestion) => format!(
+                        "Unknown metadata key \"{}\". Did you mean \"{}\"?",
+                        key, suggestion
                     ),
-                };
-                self.editor_term = Some(val);
+                    None => format!("Unknown metadata key \"{}\".", key),
+                })
             }
-            "Group" => {
-                let val = val.to_owned();
-                self.group = Some(val);
-            }
-            "Indent" => {
-                let val = match parse::parse_natural_number(val) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        die!("\"Indent\" field must be natural number. Got: {}", val; line_num)
-                    }
-                };
-                self.indent = Some(val);
-            }
-            "Markup Shorthands" => {
-                let val = match parse::parse_markup_shorthands(val) {
-                    Ok(val) => val,
-                    Err(_) => die!(
-                        concat!(
-                            "Markup shorthands metadata pieces are a markup markup shorthand category ",
-                            "and a boolean. Got: {}."
-                        ),
-                        val; line_num
-                    ),
-                };
-                self.markup_shorthands.update(&val);
-            }
-            "Infer CSS Dfns" => {
-                let val = match parse::parse_bool(val) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        die!("The \"Infer CSS Dfns\" field must be boolish. Got: {}.", val; line_num)
-                    }
-                };
-                self.infer_css_dfns = Some(val);
-            }
-            "Remove Multiple Links" => {
-                let val = match parse::parse_bool(val) {
-                    Ok(val) => val,
-                    Err(_) => {
-                        die!("The \"Remove Multiple Links\" field must be boolish. Got: {}.", val; line_num)
-                    }
-                };
-                self.remove_multiple_links = Some(val);
-            }
-            "Title" => {
-                let val = val.to_owned();
-                self.title = Some(val);
-            }
-            "TR" => {
-                let val = val.to_owned();
-                self.tr = Some(val);
-            }
-            "URL" => {
-                let val = val.to_owned();
-                self.ed = Some(val);
-            }
-            "Work Status" => {
-                let val = match parse::parse_work_status(val) {
-                    Ok(val) => val,
-                    Err(_) => die!(
-                        concat!(
-                            "Work Status must be one of (completed, stable, testing, refining, ",
-                            "revising, exploring, rewriting, abandoned). Got: {}."
-                        ),
-                        val; line_num
-                    ),
-                };
-                self.work_status = Some(val);
-            }
-            _ => die!("Unknown metadata key \"{}\".", key; line_num),
-        }
+        };
 
+        md_key.apply(self, val)?;
         self.has_keys = true;
+        self.record_entry(md_key.name, val, self.source.clone());
+
+        Ok(())
+    }
+
+    fn add_custom_data(&mut self, key: &str, val: &str) {
+        self.custom_md
+            .entry(key.to_owned())
+            .or_default()
+            .push(val.to_owned());
+    }
+
+    fn record_entry(&mut self, key: &str, val: &str, source: MetadataSource) {
+        let multi_value = is_multi_value_key(key);
+        let entry = self.entries.entry(key.to_owned()).or_default();
+
+        if !multi_value {
+            entry.values.clear();
+        }
+        entry.values.push(val.to_owned());
+        entry.source = source;
     }
 
     pub fn join(&mut self, other: Self) {
@@ -277,121 +272,121 @@ impl Metadata {
             return;
         }
 
-        // Abstract
-        self.abs.extend(other.abs.into_iter());
-        // ED
-        if other.ed.is_some() {
-            self.ed = other.ed;
-        }
-        // Level
-        if other.level.is_some() {
-            self.level = other.level;
-        }
-        // Shortname
-        if other.shortname.is_some() {
-            self.shortname = other.shortname;
-        }
-        // Status
-        if other.raw_status.is_some() {
-            self.raw_status = other.raw_status;
-        }
-        // Boilerplate
-        self.boilerplate.update(&other.boilerplate);
-        // Canonical Url
-        if other.canonical_url.is_some() {
-            self.canonical_url = other.canonical_url;
-        }
-        // Date
-        self.date = other.date;
-        // Editor
-        self.editors.extend(other.editors.into_iter());
-        // Editor Term
-        if other.editor_term.is_some() {
-            self.editor_term = other.editor_term;
-        }
-        // Group
-        if other.group.is_some() {
-            self.group = other.group;
-        }
-        // Indent
-        if other.indent.is_some() {
-            self.indent = other.indent;
-        }
-        // Infer CSS Dfns
-        if other.infer_css_dfns.is_some() {
-            self.infer_css_dfns = other.infer_css_dfns;
-        }
-        // Markup Shorthands
-        self.markup_shorthands.update(&other.markup_shorthands);
-        // Remove Multiple Links
-        if other.remove_multiple_links.is_some() {
-            self.remove_multiple_links = other.remove_multiple_links;
-        }
-        // Title
-        if other.title.is_some() {
-            self.title = other.title;
-        }
-        // TR
-        if other.tr.is_some() {
-            self.tr = other.tr;
-        }
-        // Work Status
-        if other.work_status.is_some() {
-            self.work_status = other.work_status;
-        }
-        // Custom Metadata
-        self.custom_md.extend(other.custom_md);
+        // Replay the values of the other layer, so that each key combines
+        // them the way it was declared to.
+        for (key, entry) in other.entries {
+            for val in entry.values.iter() {
+                if let Some(custom_key) = key.strip_prefix('!') {
+                    self.add_custom_data(custom_key, val);
+                } else if let Some(md_key) = registry::find_key(&key) {
+                    // The value has been parsed once already.
+                    md_key.apply(self, val).ok();
+                }
+                self.record_entry(&key, val, entry.source.clone());
+            }
+        }
     }
 
     pub fn fill_macros(&self, doc: &mut Spec) {
         let macros = &mut doc.macros;
 
-        // abstract
+        // abstractattr
         macros.insert(
-            "abstract",
-            markdown::parse(&self.abs, self.indent()).join("\n"),
+            "abstractattr".to_owned(),
+            html::escape_attr(
+                self.abs
+                    .iter()
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+                    .replace("<<", "<")
+                    .replace(">>", ">"),
+            ),
         );
-        // level
-        if let Some(ref level) = self.level {
-            macros.insert("level", level.clone());
+        // macros declared by the metadata keys
+        for md_key in registry::METADATA_KEYS.iter() {
+            if let Some(text) = md_key.macro_text(self) {
+                for name in md_key.macros.iter() {
+                    macros.insert((*name).to_owned(), text.clone());
+                }
+            }
         }
-        // shortname & vshortname
-        if let Some(ref shortname) = self.shortname {
-            macros.insert("shortname", shortname.clone());
-            macros.insert("vshortname", self.vshortname());
+        // vshortname
+        if let Some(vshortname) = self.vshortname() {
+            macros.insert("vshortname".to_owned(), vshortname);
         }
-        // longstatus
+        // status
         if let Some(ref raw_status) = self.raw_status {
-            macros.insert(
-                "longstatus",
-                (*(SHORT_TO_LONG_STATUS.get(raw_status.as_str()).unwrap())).to_string(),
-            );
+            macros.insert("status".to_owned(), raw_status.clone());
+        }
+        // longstatus
+        if let Some(long_status) = self
+            .status
+            .as_deref()
+            .and_then(|status| SHORT_TO_LONG_STATUS.get(status))
+        {
+            macros.insert("longstatus".to_owned(), (*long_status).to_owned());
         }
         // date
         macros.insert(
-            "date",
+            "date".to_owned(),
             self.date
                 .format(&format!("{} %B %Y", self.date.day()))
                 .to_string(),
         );
         // isodate
-        macros.insert("isodate", self.date.to_string());
-        // version
-        if let Some(ref ed) = self.ed {
-            macros.insert("version", ed.clone());
+        macros.insert("isodate".to_owned(), self.date.to_string());
+        // replacedby & snapshotid & snapshoturl
+        match self.warning {
+            Some(Warning::ReplacedBy(ref url)) | Some(Warning::NewVersion(ref url)) => {
+                macros.insert("replacedby".to_owned(), url.clone());
+            }
+            Some(Warning::Commit(ref id, ref url, ref replaced_by))
+            | Some(Warning::Branch(ref id, ref url, ref replaced_by)) => {
+                macros.insert("snapshotid".to_owned(), id.clone());
+                macros.insert("snapshoturl".to_owned(), url.clone());
+                if let Some(replaced_by) = replaced_by {
+                    macros.insert("replacedby".to_owned(), replaced_by.clone());
+                }
+            }
+            _ => {}
         }
-        // title & spectitle
-        if let Some(ref title) = self.title {
-            macros.insert("title", title.clone());
-            macros.insert("spectitle", title.clone());
+        // customwarningtext
+        if !self.custom_warning_text.is_empty() {
+            macros.insert(
+                "customwarningtext".to_owned(),
+                markdown::parse(
+                    &self.custom_warning_text,
+                    self.indent(),
+                    &mut doc.diagnostics,
+                )
+                .join("\n"),
+            );
         }
-        // work status
-        if let Some(ref work_status) = self.work_status {
-            macros.insert("workstatus", work_status.clone());
+        // expires & expires-dmmy & isoexpires
+        if let Some(expires) = self.expiry_date() {
+            let dmmy = expires
+                .format(&format!("{} %B %Y", expires.day()))
+                .to_string();
+            macros.insert("expires".to_owned(), dmmy.clone());
+            macros.insert("expires-dmmy".to_owned(), dmmy);
+            macros.insert("isoexpires".to_owned(), expires.to_string());
+        }
+        // custom text macros
+        for (name, text) in self.text_macros.iter() {
+            macros.insert(name.clone(), text.clone());
         }
     }
 
-    pub fn compute_implicit_metadata(&mut self) {
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn compute_implicit_metadata(&mut self, diagnostics: &mut Diagnostics) {
+        if let Some(ref raw_status) = self.raw_status {
+            match canonicalize_status(raw_status, self.group.as_deref()) {
+                Ok(status) => self.status = Some(status),
+                Err(message) => diagnostics.fatal(message, None),
+            }
+        }
         if self.canonical_url.as_ref().map_or(true, |url| url == "TR") && self.tr.is_some() {
             self.canonical_url = self.tr.clone();
         } else if self.canonical_url.as_ref().map_or(true, |url| url == "ED") && self.ed.is_some() {
@@ -400,19 +395,120 @@ impl Metadata {
         if self.editor_term.is_none() {
             self.editor_term = Some(EditorTerm::default());
         }
+        // A relative expiry date counts from the "Date" metadata.
+        if let Some(Expires::Duration(months, days)) = self.expires {
+            self.expires = self.date.checked_add(months, days).map(Expires::Date);
+        }
     }
 
-    pub fn validate(&self) {
+    pub fn validate(&self, diagnostics: &mut Diagnostics) {
         if !self.has_keys {
-            die!("No metadata provided.");
+            diagnostics.error("No metadata provided.", None);
+            return;
+        }
+
+        let missing_keys = self.missing_keys();
+        if !missing_keys.is_empty() {
+            let missing_keys = missing_keys
+                .iter()
+                .map(|key| format!("\"{}\"", key))
+                .collect::<Vec<String>>()
+                .join(", ");
+            let context = match (
+                self.status.as_ref().or(self.raw_status.as_ref()),
+                &self.group,
+            ) {
+                (Some(status), Some(group)) => {
+                    format!(" for status \"{}\" in group \"{}\"", status, group)
+                }
+                (Some(status), None) => format!(" for status \"{}\"", status),
+                _ => String::new(),
+            };
+            diagnostics.error(
+                format!("Missing required metadata{}: {}.", context, missing_keys),
+                None,
+            );
+        }
+
+        if self.warning == Some(Warning::Custom)
+            && (self.custom_warning_title.is_none() || self.custom_warning_text.is_empty())
+        {
+            diagnostics.error(
+                concat!(
+                    "\"Warning: Custom\" requires both \"Custom Warning Title\" ",
+                    "and \"Custom Warning Text\"."
+                ),
+                None,
+            );
         }
     }
 
-    pub fn vshortname(&self) -> String {
-        if let Some(ref level) = self.level {
-            format!("{}-{}", self.shortname.to_owned().unwrap(), level)
-        } else {
-            self.shortname.to_owned().unwrap()
+    // The keys required by the status and group that haven't been given.
+    fn missing_keys(&self) -> Vec<&'static str> {
+        let status = self.status.as_deref().unwrap_or_default();
+        let group = self.group.as_deref().unwrap_or_default().to_lowercase();
+
+        let required = [
+            ("Status", self.raw_status.is_some(), true),
+            ("Shortname", self.shortname.is_some(), true),
+            ("Title", self.title.is_some(), true),
+            ("Abstract", !self.abs.is_empty(), true),
+            ("Editor", !self.editors.is_empty(), true),
+            (
+                "ED",
+                self.ed.is_some(),
+                self.raw_status.is_some() && !NO_ED_STATUSES.contains(status),
+            ),
+            ("TR", self.tr.is_some(), TR_STATUSES.contains(status)),
+            (
+                "Level",
+                self.level.is_some(),
+                (self.raw_status.is_some() && !UNLEVELLED_STATUSES.contains(status))
+                    || LEVELLED_GROUPS.contains(group.as_str()),
+            ),
+        ];
+
+        required
+            .iter()
+            .filter(|(_, present, needed)| *needed && !*present)
+            .map(|(key, _, _)| *key)
+            .collect()
+    }
+
+    pub fn expiry_date(&self) -> Option<&Date> {
+        match self.expires {
+            Some(Expires::Date(ref date)) => Some(date),
+            _ => None,
+        }
+    }
+
+    // The raw merged metadata along with the layer each value came from.
+    pub fn to_json(&self) -> Value {
+        let mut metadata = Map::new();
+        let mut sources = Map::new();
+
+        for (key, entry) in self.entries.iter() {
+            let val = if is_multi_value_key(key) {
+                Value::Array(entry.values.iter().cloned().map(Value::String).collect())
+            } else {
+                Value::String(entry.values.last().cloned().unwrap_or_default())
+            };
+            metadata.insert(key.clone(), val);
+            sources.insert(key.clone(), Value::String(entry.source.to_string()));
+        }
+
+        let mut obj = Map::new();
+        obj.insert("metadata".to_owned(), Value::Object(metadata));
+        obj.insert("sources".to_owned(), Value::Object(sources));
+        Value::Object(obj)
+    }
+
+    pub fn vshortname(&self) -> Option<String> {
+        let shortname = self.shortname.as_ref()?;
+
+        match self.level {
+            Some(ref level) if !level.is_empty() => Some(format!("{}-{}", shortname, level)),
+            _ => Some(shortname.to_owned()),
         }
     }
 
@@ -421,6 +517,91 @@ impl Metadata {
     }
 }
 
+// Prefix the raw status with the megagroup of the group, and check that the
+// group is allowed to use it.
+fn canonicalize_status(raw_status: &str, group: Option<&str>) -> Result<String, String> {
+    let megagroup = group.and_then(config::megagroup_of);
+
+    if let Some((status_megagroup, _)) = raw_status.split_once('/') {
+        if !SHORT_TO_LONG_STATUS.contains_key(raw_status) {
+            return Err(unknown_status(raw_status, group, megagroup));
+        }
+        return match megagroup {
+            Some(megagroup) if megagroup != status_megagroup => Err(format!(
+                "Status \"{}\" can't be used with Group \"{}\", which is in the \"{}\" megagroup.",
+                raw_status,
+                group.unwrap_or_default(),
+                megagroup
+            )),
+            _ => Ok(raw_status.to_owned()),
+        };
+    }
+
+    // Unprefixed statuses can be used by any group.
+    if SHORT_TO_LONG_STATUS.contains_key(raw_status) {
+        return Ok(raw_status.to_owned());
+    }
+
+    if let Some(megagroup) = megagroup {
+        let status = format!("{}/{}", megagroup, raw_status);
+        if SHORT_TO_LONG_STATUS.contains_key(status.as_str()) {
+            return Ok(status);
+        }
+        return Err(unknown_status(raw_status, group, Some(megagroup)));
+    }
+
+    let mut megagroups = MEGA_GROUPS
+        .keys()
+        .filter(|megagroup| {
+            SHORT_TO_LONG_STATUS.contains_key(format!("{}/{}", megagroup, raw_status).as_str())
+        })
+        .map(|megagroup| format!("\"{}\"", megagroup))
+        .collect::<Vec<String>>();
+    megagroups.sort();
+
+    if megagroups.is_empty() {
+        Err(unknown_status(raw_status, group, None))
+    } else {
+        Err(format!(
+            concat!(
+                "Status \"{}\" only makes sense for groups in the {} megagroup(s). ",
+                "Set a \"Group\" of one of them, or prefix the status with its megagroup."
+            ),
+            raw_status,
+            megagroups.join(", ")
+        ))
+    }
+}
+
+fn unknown_status(raw_status: &str, group: Option<&str>, megagroup: Option<&str>) -> String {
+    let mut statuses = SHORT_TO_LONG_STATUS
+        .keys()
+        .filter_map(|status| match status.split_once('/') {
+            Some((status_megagroup, status)) if Some(status_megagroup) == megagroup => Some(status),
+            Some(_) => None,
+            None => Some(*status),
+        })
+        .collect::<Vec<&str>>();
+    statuses.sort_unstable();
+
+    let context = match group {
+        Some(group) => format!(" for Group \"{}\"", group),
+        None => String::new(),
+    };
+
+    format!(
+        "Unknown Status \"{}\"{}. Valid statuses are: {}.",
+        raw_status,
+        context,
+        statuses.join(", ")
+    )
+}
+
+fn is_multi_value_key(key: &str) -> bool {
+    key.starts_with('!')
+        || registry::find_key(key).is_some_and(|md_key| md_key.join != Join::Replace)
+}
+
 // Join all "group" field of metadata.
 pub fn extract_group(mds: &[&Metadata]) -> Option<String> {
     for md in mds.iter().rev() {
@@ -442,7 +623,7 @@ pub fn extract_status(mds: &[&Metadata]) -> Option<String> {
 }
 
 // TODO(#3): Figure out if we can get rid of this html-parsing-with-regexes.
-pub fn parse_metadata(lines: &[Line]) -> (Metadata, Vec<Line>) {
+pub fn parse_metadata(lines: &[Line], diagnostics: &mut Diagnostics) -> (Metadata, Vec<Line>) {
     lazy_static! {
         // regex for title
         static ref TITLE_REG: Regex = Regex::new(r"\s*<h1[^>]*>(?P<title>.*?)</h1>").unwrap();
@@ -478,26 +659,38 @@ pub fn parse_metadata(lines: &[Line]) -> (Metadata, Vec<Line>) {
             // Handle end tag.
             in_metadata = false;
         } else if in_metadata {
-            if last_key.is_some()
-                && (line.text.trim().is_empty() || START_WITH_SPACES_REG.is_match(&line.text))
-            {
+            let continued_key = last_key.as_ref().filter(|_| {
+                line.text.trim().is_empty() || START_WITH_SPACES_REG.is_match(&line.text)
+            });
+
+            // the column where the value starts
+            let mut column = 1;
+
+            let result = if let Some(key) = continued_key {
                 // If the line is empty or starts with 1+ spaces, continue the previous key.
-                md.add_data(last_key.as_ref().unwrap(), &line.text, Some(line.index));
+                md.add_data(key, &line.text)
             } else if let Some(caps) = PAIR_REG.captures(&line.text) {
                 // Handle key-val pair.
                 let key = &caps["key"];
-                let val = &caps["val"];
-                md.add_data(key, val, Some(line.index));
+                let val = caps.name("val").unwrap();
+                column += line.text[..val.start()].chars().count() as u32;
                 last_key = Some(key.to_owned());
+                md.add_data(key, val.as_str())
             } else {
                 // Meet wrong key-val pair.
-                die!("Incorrectly formatted metadata."; Some(line.index));
+                Err("Incorrectly formatted metadata.".to_owned())
+            };
+
+            if let Err(message) = result {
+                diagnostics.report_at(Severity::Error, message, line.index, column);
             }
         } else if let Some(caps) = TITLE_REG.captures(&line.text) {
             // Handle title.
             if md.title.is_none() {
                 let title = &caps["title"];
-                md.add_data("Title", title, Some(line.index));
+                if let Err(message) = md.add_data("Title", title) {
+                    diagnostics.error(message, Some(line.index));
+                }
             }
             new_lines.push(line.clone());
         } else {
@@ -508,3 +701,116 @@ pub fn parse_metadata(lines: &[Line]) -> (Metadata, Vec<Line>) {
 
     (md, new_lines)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{canonicalize_status, Metadata, MetadataSource};
+    use crate::util::message::{Diagnostics, Severity};
+
+    #[test]
+    fn test_join_sources() {
+        let mut diagnostics = Diagnostics::default();
+
+        let mut md = Metadata::from_json(
+            r#"{"Editor": "A", "Date": "2001-02-03", "Title": "Shared"}"#.to_owned(),
+            MetadataSource::File("metadata.json".to_owned()),
+            &mut diagnostics,
+        );
+
+        let mut md_doc = Metadata::new();
+        md_doc.add_data("Editor", "B").unwrap();
+        md_doc.add_data("Title", "Doc").unwrap();
+        md_doc.add_data("Title", "Doc, again").unwrap();
+
+        let mut md_cli = Metadata::with_source(MetadataSource::CommandLine);
+        md_cli.add_data("Shortname", "foo").unwrap();
+
+        md.join(md_doc);
+        md.join(md_cli);
+
+        assert!(diagnostics.worst().is_none());
+        assert_eq!(md.date.to_string(), "2001-02-03");
+        assert_eq!(md.editors.len(), 2);
+        assert_eq!(md.title, Some("Doc, again".to_owned()));
+
+        let json = md.to_json();
+        assert_eq!(json["metadata"]["Editor"], serde_json::json!(["A", "B"]));
+        assert_eq!(json["metadata"]["Title"], "Doc, again");
+        assert_eq!(json["sources"]["Date"], "file metadata.json");
+        assert_eq!(json["sources"]["Title"], "document");
+        assert_eq!(json["sources"]["Shortname"], "command line");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut md = Metadata::new();
+        md.add_data("Status", "w3c/WD").unwrap();
+        md.add_data("Group", "csswg").unwrap();
+        md.add_data("Shortname", "foo").unwrap();
+        md.add_data("Title", "Foo").unwrap();
+
+        let mut diagnostics = Diagnostics::default();
+        md.compute_implicit_metadata(&mut diagnostics);
+        md.validate(&mut diagnostics);
+
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.clone())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            messages,
+            vec![concat!(
+                "Missing required metadata for status \"w3c/WD\" in group \"csswg\": ",
+                "\"Abstract\", \"Editor\", \"ED\", \"TR\", \"Level\"."
+            )]
+        );
+        // The document can still be rendered.
+        assert_eq!(diagnostics.worst(), Some(Severity::Error));
+        assert_eq!(md.vshortname(), Some("foo".to_owned()));
+    }
+
+    #[test]
+    fn test_canonicalize_status() {
+        assert_eq!(
+            canonicalize_status("WD", Some("csswg")),
+            Ok("w3c/WD".to_owned())
+        );
+        assert_eq!(canonicalize_status("LS", Some("test")), Ok("LS".to_owned()));
+        assert_eq!(canonicalize_status("w3c/CR", None), Ok("w3c/CR".to_owned()));
+        assert_eq!(
+            canonicalize_status("iso/WD", Some("csswg")),
+            Err(concat!(
+                "Status \"iso/WD\" can't be used with Group \"csswg\", ",
+                "which is in the \"w3c\" megagroup."
+            )
+            .to_owned())
+        );
+        assert!(canonicalize_status("WD", Some("test"))
+            .unwrap_err()
+            .contains("\"fido\", \"iso\", \"w3c\" megagroup(s)"));
+        assert!(canonicalize_status("STAGE0", Some("csswg"))
+            .unwrap_err()
+            .starts_with("Unknown Status \"STAGE0\" for Group \"csswg\". Valid statuses are: "));
+    }
+
+    #[test]
+    fn test_load_defaults() {
+        for path in glob::glob("boilerplate/**/defaults*.include").unwrap() {
+            let path = path.unwrap();
+            let mut diagnostics = Diagnostics::default();
+            Metadata::from_json(
+                fs::read_to_string(&path).unwrap(),
+                MetadataSource::Default,
+                &mut diagnostics,
+            );
+
+            let messages = diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.clone())
+                .collect::<Vec<String>>();
+            assert!(messages.is_empty(), "{}: {:?}", path.display(), messages);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use regex::Regex;
+use std::convert::TryFrom;
 
 use crate::util::boolish::boolish_to_bool;
 use crate::util::boolset::BoolSet;
@@ -50,6 +51,7 @@ pub fn parse_boilerplate(val: &str) -> Result<BoolSet<String>, &'static str> {
     Ok(boilerplate)
 }
 
+#[allow(clippy::needless_match)]
 pub fn parse_date(val: &str) -> ParseResult {
     if val == "now" {
         Ok(Date::now())
@@ -61,6 +63,53 @@ pub fn parse_date(val: &str) -> ParseResult {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expires {
+    Never,
+    Date(Date),
+    // months and days after the "Date" metadata
+    Duration(u32, u64),
+}
+
+pub fn parse_expires(val: &str) -> Result<Expires, &'static str> {
+    // <expires> := "never" | <boolish-false> | "now" | <date> | <iso-8601-duration>
+
+    lazy_static! {
+        static ref DURATION_REG: Regex =
+            Regex::new(r"^P(?:(\d+)Y)?(?:(\d+)M)?(?:(\d+)W)?(?:(\d+)D)?$").unwrap();
+    }
+
+    let val = val.trim();
+
+    if val.eq_ignore_ascii_case("never") || boolish_to_bool(val) == Ok(false) {
+        return Ok(Expires::Never);
+    }
+
+    if let Ok(date) = parse_date(val) {
+        return Ok(Expires::Date(date));
+    }
+
+    match DURATION_REG.captures(val) {
+        Some(ref caps) if val != "P" => {
+            let num = |index: usize| -> Result<u64, &'static str> {
+                match caps.get(index) {
+                    Some(m) => m.as_str().parse().map_err(|_| "wrong duration format"),
+                    None => Ok(0),
+                }
+            };
+
+            let months = num(1)? * 12 + num(2)?;
+            let days = num(3)? * 7 + num(4)?;
+
+            match u32::try_from(months) {
+                Ok(months) => Ok(Expires::Duration(months, days)),
+                Err(_) => Err("wrong duration format"),
+            }
+        }
+        _ => Err("wrong expires format"),
+    }
+}
+
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct Editor {
     pub name: String,
@@ -90,7 +139,7 @@ pub fn parse_editor(val: &str) -> Result<Editor, &'static str> {
         // regex for link
         static ref LINK_REG: Regex = Regex::new(r"^\w+:").unwrap();
         // regex for email
-        static ref EMAIL_REG: Regex = Regex::new(r"^\w+@.+\..+").unwrap();
+        static ref EMAIL_REG: Regex = Regex::new(r"^[^\s@]+@\S+\.\S+$").unwrap();
         // regex for name with id
         static ref NAME_WITH_ID_REG: Regex = Regex::new(r"\s\d+$").unwrap();
     }
@@ -121,7 +170,7 @@ pub fn parse_editor(val: &str) -> Result<Editor, &'static str> {
         .cloned()
         .filter(|piece| {
             if W3C_ID_REG.is_match(piece) && editor.w3c_id.is_none() {
-                editor.w3c_id = Some((&piece[6..]).to_owned());
+                editor.w3c_id = Some(piece[6..].to_owned());
                 false
             } else {
                 true
@@ -240,6 +289,36 @@ pub fn parse_editor_term(val: &str) -> Result<EditorTerm, &'static str> {
     }
 }
 
+pub fn parse_issue_tracking(val: &str) -> Result<(String, String), &'static str> {
+    // <issue-tracking> := <label> <url>
+
+    match val.trim().rsplit_once(char::is_whitespace) {
+        Some((label, url)) if !label.trim().is_empty() => {
+            Ok((label.trim().to_owned(), url.to_owned()))
+        }
+        _ => Err("wrong issue tracking format"),
+    }
+}
+
+pub fn parse_repository(val: &str) -> Result<String, &'static str> {
+    // <repository> := <url> | <github-owner> "/" <github-repo>
+
+    lazy_static! {
+        static ref URL_REG: Regex = Regex::new(r"^\w+://\S+$").unwrap();
+        static ref GITHUB_REG: Regex = Regex::new(r"^[\w.-]+/[\w.-]+$").unwrap();
+    }
+
+    let val = val.trim();
+
+    if URL_REG.is_match(val) {
+        Ok(val.to_owned())
+    } else if GITHUB_REG.is_match(val) {
+        Ok(format!("https://github.com/{}", val))
+    } else {
+        Err("wrong repository format")
+    }
+}
+
 pub fn parse_level(val: &str) -> String {
     if val == "none" {
         String::new()
@@ -248,6 +327,36 @@ pub fn parse_level(val: &str) -> String {
     }
 }
 
+pub fn parse_metadata_include(val: &str) -> Result<BoolSet<String>, &'static str> {
+    // <metadata-include> := <pair> ("," <pair>)*
+    // <pair> := <metadata-key> <boolish>
+
+    let mut metadata_include = BoolSet::<String>::new_with_default(true);
+
+    for item in val.split(',').map(|item| item.trim()) {
+        let (key, on_off) = match item.rsplit_once(char::is_whitespace) {
+            Some((key, on_off)) if !key.trim().is_empty() => (key.trim(), on_off),
+            _ => return Err("wrong metadata include piece format"),
+        };
+
+        let on_off = boolish_to_bool(on_off)?;
+        metadata_include.insert(key.to_owned(), on_off);
+    }
+
+    Ok(metadata_include)
+}
+
+pub fn parse_metadata_order(val: &str) -> Vec<String> {
+    // <metadata-order> := <item> ("," <item>)*
+    // <item> := "*" | "!*" | <metadata-key>
+
+    val.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
 pub fn parse_markup_shorthands(val: &str) -> Result<BoolSet<String>, &'static str> {
     // <markup-shorthands> := <pair> ("," <pair>)*
     // <pair> := <markup-category> <boolish>
@@ -276,22 +385,157 @@ pub fn parse_markup_shorthands(val: &str) -> Result<BoolSet<String>, &'static st
     Ok(markup_shorthands)
 }
 
-pub fn parse_work_status(val: &str) -> Result<String, &'static str> {
-    let val = val.to_lowercase();
+pub fn parse_text_macro(val: &str) -> Result<(String, String), &'static str> {
+    // <text-macro> := <macro-name> <replacement-text>
+    // <macro-name> := [A-Z0-9-]+, with at least one letter
+
+    lazy_static! {
+        static ref REG: Regex = Regex::new(r"^([A-Z0-9-]*[A-Z][A-Z0-9-]*)\s+(.*)$").unwrap();
+    }
+
+    match REG.captures(val.trim()) {
+        Some(caps) => Ok((caps[1].to_lowercase(), caps[2].to_owned())),
+        None => Err("wrong text macro format"),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    Obsolete,
+    NotReady,
+    ReplacedBy(String),
+    NewVersion(String),
+    // snapshot id, snapshot url, replaced by
+    Commit(String, String, Option<String>),
+    Branch(String, String, Option<String>),
+    Custom,
+}
+
+impl Warning {
+    // The name of the boilerplate file for this warning.
+    pub fn boilerplate_name(&self) -> &'static str {
+        match self {
+            Warning::Obsolete => "warning-obsolete",
+            Warning::NotReady => "warning-not-ready",
+            Warning::ReplacedBy(_) => "warning-replaced-by",
+            Warning::NewVersion(_) => "warning-new-version",
+            Warning::Commit(..) => "warning-commit",
+            Warning::Branch(..) => "warning-branch",
+            Warning::Custom => "warning-custom",
+        }
+    }
+}
+
+pub fn parse_warning(val: &str) -> Result<Warning, &'static str> {
+    // <warning> := "Obsolete" | "Not Ready" | "Custom"
+    //            | "Replaced By" <url> | "New Version" <url>
+    //            | ("Commit" | "Branch") <id> <url> ("replaced by" <url>)?
+
+    lazy_static! {
+        static ref SNAPSHOT_REG: Regex =
+            Regex::new(r"(?i)^(commit|branch)\s+(\S+)\s+(\S+)(?:\s+replaced\s+by\s+(\S+))?$")
+                .unwrap();
+        static ref REPLACED_REG: Regex =
+            Regex::new(r"(?i)^(replaced\s+by|new\s+version)\s+(\S+)$").unwrap();
+    }
+
+    let val = val.trim();
+
+    match val.to_lowercase().as_str() {
+        "obsolete" => return Ok(Warning::Obsolete),
+        "not ready" => return Ok(Warning::NotReady),
+        "custom" => return Ok(Warning::Custom),
+        _ => {}
+    }
+
+    if let Some(caps) = SNAPSHOT_REG.captures(val) {
+        let id = caps[2].to_owned();
+        let url = caps[3].to_owned();
+        let replaced_by = caps.get(4).map(|m| m.as_str().to_owned());
+
+        return if caps[1].eq_ignore_ascii_case("commit") {
+            Ok(Warning::Commit(id, url, replaced_by))
+        } else {
+            Ok(Warning::Branch(id, url, replaced_by))
+        };
+    }
+
+    if let Some(caps) = REPLACED_REG.captures(val) {
+        let url = caps[2].to_owned();
+
+        return if caps[1].to_lowercase().starts_with("replaced") {
+            Ok(Warning::ReplacedBy(url))
+        } else {
+            Ok(Warning::NewVersion(url))
+        };
+    }
+
+    Err("wrong warning format")
+}
+
+// Match the value against a fixed set of lowercase options, ignoring case.
+pub fn parse_enum(val: &str, options: &[&str]) -> Result<String, &'static str> {
+    let val = val.trim().to_lowercase();
+
+    if options.contains(&val.as_str()) {
+        Ok(val)
+    } else {
+        Err("no such option")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoftBool {
+    Yes,
+    No,
+    // on if the data for it is available
+    Maybe,
+}
+
+pub fn parse_soft_bool(val: &str) -> Result<SoftBool, &'static str> {
+    // <soft-bool> := <boolish> | "maybe" | "if possible" | "if needed"
+
+    match val.trim().to_lowercase().as_str() {
+        "maybe" | "if possible" | "if needed" => Ok(SoftBool::Maybe),
+        val => match boolish_to_bool(val)? {
+            true => Ok(SoftBool::Yes),
+            false => Ok(SoftBool::No),
+        },
+    }
+}
+
+pub fn parse_toggles(val: &str) -> Result<BoolSet<String>, &'static str> {
+    // <toggles> := <pair> ("," <pair>)*
+    // <pair> := <name> <boolish>
 
-    match val.as_str() {
-        "completed" | "stable" | "testing" | "refining" | "revising" | "exploring"
-        | "rewriting" | "abandoned" => Ok(val),
-        _ => Err("no such work status"),
+    let mut toggles = BoolSet::<String>::new_with_default(false);
+
+    for item in val.split(',').map(|item| item.trim()) {
+        let (name, on_off) = match item.rsplit_once(char::is_whitespace) {
+            Some((name, on_off)) if !name.trim().is_empty() => (name.trim(), on_off),
+            _ => return Err("wrong toggle piece format"),
+        };
+
+        let on_off = boolish_to_bool(on_off)?;
+        toggles.insert(name.to_lowercase(), on_off);
     }
+
+    Ok(toggles)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_boilerplate, parse_editor, parse_editor_term, Editor, EditorTerm};
+    use super::{
+        parse_boilerplate, parse_editor, parse_editor_term, parse_enum, parse_expires,
+        parse_issue_tracking, parse_metadata_include, parse_repository, parse_soft_bool,
+        parse_text_macro, parse_toggles, parse_warning, Editor, EditorTerm, Expires, SoftBool,
+        Warning,
+    };
+    use crate::util::date::Date;
     use std::collections::BTreeMap;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_parse_boilerplate() {
         {
             let result = parse_boilerplate("");
@@ -501,4 +745,155 @@ mod tests {
             assert_eq!(result, target);
         }
     }
+
+    #[test]
+    fn test_parse_expires() {
+        let date = |val: &str| Date::parse_from_str(val, "%Y-%m-%d").unwrap();
+
+        let cases: BTreeMap<&'static str, Result<Expires, &'static str>> = btreemap! {
+            "never" => Ok(Expires::Never),
+            "no" => Ok(Expires::Never),
+            "2030-01-01" => Ok(Expires::Date(date("2030-01-01"))),
+            "P1W" => Ok(Expires::Duration(0, 7)),
+            "P60Y" => Ok(Expires::Duration(720, 0)),
+            "P1Y2M3W4D" => Ok(Expires::Duration(14, 25)),
+            "P" => Err("wrong expires format"),
+            "1W" => Err("wrong expires format"),
+            "soon" => Err("wrong expires format"),
+        };
+
+        for (val, target) in cases {
+            let result = parse_expires(val);
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn test_parse_issue_tracking() {
+        let cases: BTreeMap<&'static str, Result<(String, String), &'static str>> = btreemap! {
+            "GitHub https://github.com/a/b/issues" => Ok((
+                "GitHub".to_owned(),
+                "https://github.com/a/b/issues".to_owned(),
+            )),
+            "Inline In Spec #issues-index" => Ok((
+                "Inline In Spec".to_owned(),
+                "#issues-index".to_owned(),
+            )),
+            "https://github.com/a/b/issues" => Err("wrong issue tracking format"),
+        };
+
+        for (val, target) in cases {
+            let result = parse_issue_tracking(val);
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn test_parse_metadata_include() {
+        {
+            let result = parse_metadata_include("Foo no, Editor no, Version History yes");
+            assert!(result.is_ok());
+            if let Ok(result) = result {
+                assert!(!result.get("Foo"));
+                assert!(!result.get("Editor"));
+                assert!(result.get("Version History"));
+                assert!(result.get("Baz"));
+            }
+        }
+        {
+            let result = parse_metadata_include("Foo");
+            assert_eq!(result, Err("wrong metadata include piece format"));
+        }
+        {
+            let result = parse_metadata_include("Foo bar");
+            assert_eq!(result, Err("wrong boolish format"));
+        }
+    }
+
+    #[test]
+    fn test_parse_repository() {
+        let cases: BTreeMap<&'static str, Result<String, &'static str>> = btreemap! {
+            "tabatkins/bikeshed" => Ok("https://github.com/tabatkins/bikeshed".to_owned()),
+            "https://example.com/repo" => Ok("https://example.com/repo".to_owned()),
+            "bikeshed" => Err("wrong repository format"),
+        };
+
+        for (val, target) in cases {
+            let result = parse_repository(val);
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn test_parse_text_macro() {
+        let cases: BTreeMap<&'static str, Result<(String, String), &'static str>> = btreemap! {
+            "FOO bar" => Ok(("foo".to_owned(), "bar".to_owned())),
+            "BAZ qux qux qux" => Ok(("baz".to_owned(), "qux qux qux".to_owned())),
+            "X-1 [Y]" => Ok(("x-1".to_owned(), "[Y]".to_owned())),
+            "foo bar" => Err("wrong text macro format"),
+            "123 bar" => Err("wrong text macro format"),
+            "FOO" => Err("wrong text macro format"),
+        };
+
+        for (val, target) in cases {
+            let result = parse_text_macro(val);
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn test_parse_warning() {
+        let cases: BTreeMap<&'static str, Result<Warning, &'static str>> = btreemap! {
+            "Obsolete" => Ok(Warning::Obsolete),
+            "not ready" => Ok(Warning::NotReady),
+            "Custom" => Ok(Warning::Custom),
+            "Replaced By https://a.b/" => Ok(Warning::ReplacedBy("https://a.b/".to_owned())),
+            "New Version https://a.b/" => Ok(Warning::NewVersion("https://a.b/".to_owned())),
+            "Commit 1a2b3c https://a.b/commit" => Ok(Warning::Commit(
+                "1a2b3c".to_owned(),
+                "https://a.b/commit".to_owned(),
+                None,
+            )),
+            "Branch foo https://a.b/foo replaced by https://a.b/" => Ok(Warning::Branch(
+                "foo".to_owned(),
+                "https://a.b/foo".to_owned(),
+                Some("https://a.b/".to_owned()),
+            )),
+            "Replaced By" => Err("wrong warning format"),
+            "Commit 1a2b3c" => Err("wrong warning format"),
+            "Obsolete now" => Err("wrong warning format"),
+        };
+
+        for (val, target) in cases {
+            let result = parse_warning(val);
+            assert_eq!(result, target);
+        }
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let options = ["current", "snapshot"];
+        assert_eq!(parse_enum("Snapshot", &options), Ok("snapshot".to_owned()));
+        assert_eq!(parse_enum("dated", &options), Err("no such option"));
+    }
+
+    #[test]
+    fn test_parse_soft_bool() {
+        assert_eq!(parse_soft_bool("yes"), Ok(SoftBool::Yes));
+        assert_eq!(parse_soft_bool("off"), Ok(SoftBool::No));
+        assert_eq!(parse_soft_bool("If Possible"), Ok(SoftBool::Maybe));
+        assert!(parse_soft_bool("sometimes").is_err());
+    }
+
+    #[test]
+    fn test_parse_toggles() {
+        let toggles = parse_toggles("missing-example-ids yes, Accidental-2119 no").unwrap();
+        assert!(toggles.get("missing-example-ids"));
+        assert!(!toggles.get("accidental-2119"));
+        assert!(!toggles.get("broken-links"));
+        assert_eq!(
+            parse_toggles("missing-example-ids").unwrap_err(),
+            "wrong toggle piece format"
+        );
+    }
 }
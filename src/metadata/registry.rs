@@ -0,0 +1,515 @@
+use indexmap::map::IndexMap;
+
+use super::parse::{self, Editor, EditorTerm, Expires, SoftBool, Warning};
+use super::Metadata;
+use crate::util::boolset::BoolSet;
+use crate::util::date::Date;
+
+// How the values of a key combine, both within a layer and across layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Join {
+    // A later value replaces the earlier one.
+    Replace,
+    // Every value is kept.
+    Append,
+    // A later set of booleans updates the earlier one.
+    MergeBoolSet,
+}
+
+pub struct MetadataKey {
+    pub name: &'static str,
+    pub join: Join,
+    // text macros filled with the value of this key
+    pub macros: &'static [&'static str],
+    apply: fn(&mut Metadata, &str) -> Result<(), String>,
+    macro_text: fn(&Metadata) -> Option<String>,
+}
+
+impl MetadataKey {
+    // Parse the value and store it in the metadata.
+    pub fn apply(&self, md: &mut Metadata, val: &str) -> Result<(), String> {
+        (self.apply)(md, val)
+    }
+
+    pub fn macro_text(&self, md: &Metadata) -> Option<String> {
+        (self.macro_text)(md)
+    }
+}
+
+trait Replace<T> {
+    fn replace(&mut self, val: T);
+}
+
+impl<T> Replace<T> for Option<T> {
+    fn replace(&mut self, val: T) {
+        *self = Some(val);
+    }
+}
+
+impl Replace<bool> for bool {
+    fn replace(&mut self, val: bool) {
+        *self = val;
+    }
+}
+
+impl Replace<Date> for Date {
+    fn replace(&mut self, val: Date) {
+        *self = val;
+    }
+}
+
+impl<T> Replace<Vec<T>> for Vec<T> {
+    fn replace(&mut self, val: Vec<T>) {
+        *self = val;
+    }
+}
+
+trait Append<T> {
+    fn append(&mut self, val: T);
+}
+
+impl<T> Append<T> for Vec<T> {
+    fn append(&mut self, val: T) {
+        self.push(val);
+    }
+}
+
+impl<T> Append<Vec<T>> for Vec<T> {
+    fn append(&mut self, val: Vec<T>) {
+        self.extend(val);
+    }
+}
+
+impl Append<(String, String)> for IndexMap<String, String> {
+    fn append(&mut self, (key, val): (String, String)) {
+        self.insert(key, val);
+    }
+}
+
+trait MacroText {
+    fn macro_text(&self) -> Option<String>;
+}
+
+impl MacroText for Option<String> {
+    fn macro_text(&self) -> Option<String> {
+        self.clone()
+    }
+}
+
+macro_rules! store {
+    (Replace, $field:expr, $val:expr) => {
+        Replace::replace(&mut $field, $val)
+    };
+    (Append, $field:expr, $val:expr) => {
+        Append::append(&mut $field, $val)
+    };
+    (MergeBoolSet, $field:expr, $val:expr) => {
+        $field.update(&$val)
+    };
+}
+
+macro_rules! macro_text {
+    ($field:ident) => {
+        |_| None
+    };
+    ($field:ident, $($macro_name:expr),+) => {
+        |md| MacroText::macro_text(&md.$field)
+    };
+}
+
+macro_rules! metadata_keys {
+    ($(
+        $name:expr => $field:ident: $parser:ident, $join:ident
+            $(, macros($($macro_name:expr),+))?;
+    )*) => {
+        pub static METADATA_KEYS: &[MetadataKey] = &[
+            $(
+                MetadataKey {
+                    name: $name,
+                    join: Join::$join,
+                    macros: &[$($($macro_name),+)?],
+                    apply: |md, val| {
+                        let val = $parser($name, val)?;
+                        store!($join, md.$field, val);
+                        Ok(())
+                    },
+                    macro_text: macro_text!($field $($(, $macro_name)+)?),
+                },
+            )*
+        ];
+    };
+}
+
+metadata_keys! {
+    // key => field: parser, join strategy, macros
+    "Abstract" => abs: string, Append;
+    "Advisement Class" => advisement_class: string, Replace;
+    "Assertion Class" => assertion_class: string, Replace;
+    "Assume Explicit For" => assume_explicit_for: boolean, Replace;
+    "Audience" => audience: list, Append;
+    "Block Elements" => block_elements: list, Append;
+    "Boilerplate" => boilerplate: boilerplate, MergeBoolSet;
+    "Can I Use URL" => can_i_use_urls: url, Append;
+    "Canonical URL" => canonical_url: url, Replace;
+    "Complain About" => complain_about: toggles, MergeBoolSet;
+    "Custom Warning Text" => custom_warning_text: string, Append;
+    "Custom Warning Title" => custom_warning_title: string, Replace,
+        macros("customwarningtitle");
+    "Date" => date: date, Replace;
+    "Deadline" => deadline: date, Replace;
+    "Default Biblio Display" => default_biblio_display: biblio_display, Replace;
+    "Default Biblio Status" => default_biblio_status: biblio_status, Replace;
+    "Default Highlight" => default_highlight: string, Replace;
+    "Default Ref Status" => default_ref_status: ref_status, Replace;
+    "ED" => ed: string, Replace, macros("version");
+    "Editor" => editors: editor, Append;
+    "Editor Term" => editor_term: editor_term, Replace;
+    "Expires" => expires: expires, Replace;
+    "External Infotrees" => external_infotrees: toggles, MergeBoolSet;
+    "Favicon" => favicon: url, Replace;
+    "Force Crossorigin" => force_crossorigin: boolean, Replace;
+    "Former Editor" => former_editors: editor, Append;
+    "Group" => group: string, Replace;
+    "H1" => h1: string, Replace;
+    "Ignore Can I Use URL Failure" => ignore_can_i_use_url_failures: url, Append;
+    "Ignore MDN Failure" => ignore_mdn_failures: string, Append;
+    "Ignored Terms" => ignored_terms: list, Append;
+    "Ignored Vars" => ignored_vars: list, Append;
+    "Implementation Report" => implementation_report: url, Replace;
+    "Include Can I Use Panels" => include_can_i_use_panels: boolean, Replace;
+    "Include MDN Panels" => include_mdn_panels: soft_boolean, Replace;
+    "Indent" => indent: natural_number, Replace;
+    "Infer CSS Dfns" => infer_css_dfns: boolean, Replace;
+    "Informative Classes" => informative_classes: list, Append;
+    "Inline GitHub Issues" => inline_github_issues: inline_github_issues, Replace;
+    "Issue Class" => issue_class: string, Replace;
+    "Issue Tracker Template" => issue_tracker_template: string, Replace;
+    "Issue Tracking" => issue_tracking: issue_tracking, Append;
+    "Level" => level: level, Replace, macros("level");
+    "Line Numbers" => line_numbers: boolean, Replace;
+    "Link Defaults" => link_defaults: string, Append;
+    "Local Boilerplate" => local_boilerplate: toggles, MergeBoolSet;
+    "Logo" => logo: url, Replace;
+    "Mailing List" => mailing_list: string, Replace;
+    "Mailing List Archives" => mailing_list_archives: url, Replace;
+    "Markup Shorthands" => markup_shorthands: markup_shorthands, MergeBoolSet;
+    "Max ToC Depth" => max_toc_depth: natural_number, Replace;
+    "Metadata Include" => metadata_include: metadata_include, MergeBoolSet;
+    "Metadata Order" => metadata_order: list, Replace;
+    "No Abstract" => no_abstract: boolean, Replace;
+    "No Editor" => no_editor: boolean, Replace;
+    "Note Class" => note_class: string, Replace;
+    "Opaque Elements" => opaque_elements: list, Append;
+    "Prepare For TR" => prepare_for_tr: boolean, Replace;
+    "Previous Version" => previous_versions: url, Append;
+    "Remove Multiple Links" => remove_multiple_links: boolean, Replace;
+    "Repository" => repository: repository, Replace;
+    "Required IDs" => required_ids: string, Append;
+    "Shortname" => shortname: string, Replace, macros("shortname");
+    "Slim Build Artifact" => slim_build_artifact: boolean, Replace;
+    "Status" => raw_status: string, Replace;
+    "Status Text" => status_text: string, Append;
+    "Test Suite" => test_suite: url, Replace;
+    "Text Macro" => text_macros: text_macro, Append;
+    "Title" => title: string, Replace, macros("title", "spectitle");
+    "Toggle Diffs" => toggle_diffs: boolean, Replace;
+    "TR" => tr: url, Replace;
+    "Tracking Vector Alt Text" => tracking_vector_alt_text: string, Replace;
+    "Tracking Vector Class" => tracking_vector_class: string, Replace;
+    "Tracking Vector Image" => tracking_vector_image: url, Replace;
+    "Tracking Vector Image Height" => tracking_vector_image_height: natural_number, Replace;
+    "Tracking Vector Image Width" => tracking_vector_image_width: natural_number, Replace;
+    "Tracking Vector Title" => tracking_vector_title: string, Replace;
+    "Translate IDs" => translate_ids: string, Append;
+    "Translation" => translations: string, Append;
+    "URL" => ed: string, Replace, macros("version");
+    "Use <I> Autolinks" => use_i_autolinks: boolean, Replace;
+    "Use Dfn Panels" => use_dfn_panels: boolean, Replace;
+    "Version History" => version_history: url, Append;
+    "Warning" => warning: warning, Replace;
+    "Work Status" => work_status: work_status, Replace, macros("workstatus");
+    "WPT Display" => wpt_display: wpt_display, Replace;
+    "WPT Path Prefix" => wpt_path_prefix: string, Replace;
+}
+
+// Find the definition of a key. Keys are case-insensitive.
+pub fn find_key(name: &str) -> Option<&'static MetadataKey> {
+    METADATA_KEYS
+        .iter()
+        .find(|md_key| md_key.name.eq_ignore_ascii_case(name))
+}
+
+// Find the known key that the unknown one is most likely a misspelling of.
+pub fn suggest_key(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+
+    METADATA_KEYS
+        .iter()
+        .map(|md_key| {
+            (
+                edit_distance(&name, &md_key.name.to_lowercase()),
+                md_key.name,
+            )
+        })
+        .filter(|(distance, md_name)| *distance <= (md_name.len() / 3).max(2))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, md_name)| md_name)
+}
+
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs = rhs.chars().collect::<Vec<char>>();
+    let mut row = (0..=rhs.len()).collect::<Vec<usize>>();
+
+    for (i, lc) in lhs.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, rc) in rhs.iter().enumerate() {
+            let curr = row[j + 1];
+            row[j + 1] = if lc == *rc {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(curr)
+            };
+            prev = curr;
+        }
+    }
+
+    row[rhs.len()]
+}
+
+// parsers
+
+fn string(_: &str, val: &str) -> Result<String, String> {
+    Ok(val.to_owned())
+}
+
+fn url(key: &str, val: &str) -> Result<String, String> {
+    let val = val.trim();
+
+    if val.is_empty() || val.contains(char::is_whitespace) {
+        return Err(format!(
+            "The \"{}\" field must be a url. Got: {}.",
+            key, val
+        ));
+    }
+
+    Ok(val.to_owned())
+}
+
+fn list(_: &str, val: &str) -> Result<Vec<String>, String> {
+    Ok(parse::parse_metadata_order(val))
+}
+
+fn boolean(key: &str, val: &str) -> Result<bool, String> {
+    parse::parse_bool(val)
+        .map_err(|_| format!("The \"{}\" field must be boolish. Got: {}.", key, val))
+}
+
+fn soft_boolean(key: &str, val: &str) -> Result<SoftBool, String> {
+    parse::parse_soft_bool(val).map_err(|_| {
+        format!(
+            "The \"{}\" field must be boolish or \"maybe\". Got: {}.",
+            key, val
+        )
+    })
+}
+
+// Accept one of a fixed set of values.
+fn one_of(key: &str, val: &str, options: &[&str]) -> Result<String, String> {
+    parse::parse_enum(val, options).map_err(|_| {
+        format!(
+            "The \"{}\" field must be one of ({}). Got: {}.",
+            key,
+            options.join(", "),
+            val
+        )
+    })
+}
+
+fn biblio_display(key: &str, val: &str) -> Result<String, String> {
+    one_of(key, val, &["direct", "index", "inline"])
+}
+
+fn biblio_status(key: &str, val: &str) -> Result<String, String> {
+    one_of(key, val, &["current", "dated"])
+}
+
+fn ref_status(key: &str, val: &str) -> Result<String, String> {
+    one_of(key, val, &["current", "snapshot"])
+}
+
+fn wpt_display(key: &str, val: &str) -> Result<String, String> {
+    one_of(key, val, &["none", "inline", "open", "closed"])
+}
+
+fn work_status(key: &str, val: &str) -> Result<String, String> {
+    one_of(
+        key,
+        val,
+        &[
+            "completed",
+            "stable",
+            "testing",
+            "refining",
+            "revising",
+            "exploring",
+            "rewriting",
+            "abandoned",
+        ],
+    )
+}
+
+fn inline_github_issues(key: &str, val: &str) -> Result<String, String> {
+    // Turning it on shows the full issues.
+    match parse::parse_bool(val) {
+        Ok(true) => Ok("full".to_owned()),
+        Ok(false) => Ok("no".to_owned()),
+        Err(_) => one_of(key, val, &["no", "title", "full"]),
+    }
+}
+
+fn natural_number(key: &str, val: &str) -> Result<u32, String> {
+    parse::parse_natural_number(val)
+        .map_err(|_| format!("\"{}\" field must be natural number. Got: {}", key, val))
+}
+
+fn date(key: &str, val: &str) -> Result<Date, String> {
+    parse::parse_date(val)
+        .map_err(|_| format!("The \"{}\" field must be in the format YYYY-MM-DD.", key))
+}
+
+fn expires(key: &str, val: &str) -> Result<Expires, String> {
+    parse::parse_expires(val).map_err(|_| {
+        format!(
+            concat!(
+                "The \"{}\" field must be a date in the format YYYY-MM-DD, ",
+                "a duration like P1Y2M3W4D, or \"never\". Got: {}."
+            ),
+            key, val
+        )
+    })
+}
+
+fn editor(key: &str, val: &str) -> Result<Editor, String> {
+    parse::parse_editor(val).map_err(|_| {
+        format!(
+            concat!(
+                "\"{}\" format is \"<name>, <affiliation>?, <email-or-contact-page>?\". ",
+                "Got: {}."
+            ),
+            key, val
+        )
+    })
+}
+
+fn editor_term(key: &str, val: &str) -> Result<EditorTerm, String> {
+    parse::parse_editor_term(val).map_err(|_| {
+        format!(
+            "\"{}\" format is \"<singular-term>, <plural-term>\". Got: {}.",
+            key, val
+        )
+    })
+}
+
+fn boilerplate(_: &str, val: &str) -> Result<BoolSet<String>, String> {
+    parse::parse_boilerplate(val).map_err(|_| {
+        format!(
+            concat!(
+                "Boilerplate metadata pieces are a boilerplate label and a boolean. ",
+                "Got: {}."
+            ),
+            val
+        )
+    })
+}
+
+fn markup_shorthands(_: &str, val: &str) -> Result<BoolSet<String>, String> {
+    parse::parse_markup_shorthands(val).map_err(|_| {
+        format!(
+            concat!(
+                "Markup shorthands metadata pieces are a markup markup shorthand category ",
+                "and a boolean. Got: {}."
+            ),
+            val
+        )
+    })
+}
+
+fn toggles(key: &str, val: &str) -> Result<BoolSet<String>, String> {
+    parse::parse_toggles(val)
+        .map_err(|_| format!("\"{}\" pieces are a name and a boolean. Got: {}.", key, val))
+}
+
+fn metadata_include(_: &str, val: &str) -> Result<BoolSet<String>, String> {
+    parse::parse_metadata_include(val).map_err(|_| {
+        format!(
+            "Metadata Include pieces are a metadata key and a boolean. Got: {}.",
+            val
+        )
+    })
+}
+
+fn level(_: &str, val: &str) -> Result<String, String> {
+    Ok(parse::parse_level(val))
+}
+
+fn text_macro(_: &str, val: &str) -> Result<(String, String), String> {
+    parse::parse_text_macro(val).map_err(|_| {
+        format!(
+            concat!(
+                "Text Macro lines must contain a macro name (all-caps) ",
+                "followed by the replacement text. Got: {}."
+            ),
+            val
+        )
+    })
+}
+
+fn warning(_: &str, val: &str) -> Result<Warning, String> {
+    parse::parse_warning(val).map_err(|_| {
+        format!(
+            concat!(
+                "Warning metadata must be one of Obsolete, Not Ready, ",
+                "Replaced By <url>, New Version <url>, Commit <id> <url>, ",
+                "Branch <name> <url> or Custom. Got: {}."
+            ),
+            val
+        )
+    })
+}
+
+fn issue_tracking(key: &str, val: &str) -> Result<(String, String), String> {
+    parse::parse_issue_tracking(val)
+        .map_err(|_| format!("\"{}\" format is \"<label> <url>\". Got: {}.", key, val))
+}
+
+fn repository(key: &str, val: &str) -> Result<String, String> {
+    parse::parse_repository(val).map_err(|_| {
+        format!(
+            "\"{}\" must be a url or a GitHub \"owner/repo\". Got: {}.",
+            key, val
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_key, suggest_key, Join};
+
+    #[test]
+    fn test_find_key() {
+        assert_eq!(find_key("work status").unwrap().name, "Work Status");
+        assert_eq!(find_key("ed").unwrap().name, "ED");
+        assert_eq!(find_key("Editor").unwrap().join, Join::Append);
+        assert!(find_key("Wrok Status").is_none());
+    }
+
+    #[test]
+    fn test_suggest_key() {
+        assert_eq!(suggest_key("Wrok Status"), Some("Work Status"));
+        assert_eq!(suggest_key("editors"), Some("Editor"));
+        assert_eq!(suggest_key("Shortnam"), Some("Shortname"));
+        assert_eq!(suggest_key("Something Else"), None);
+    }
+}
@@ -0,0 +1,19 @@
+// Reload the page whenever the preview server has rendered a new version.
+(() => {
+  let version = null;
+
+  const check = () => {
+    fetch("/__version", { cache: "no-store" })
+      .then((response) => response.text())
+      .then((text) => {
+        if (version !== null && text !== version) {
+          location.reload();
+        }
+        version = text;
+      })
+      .catch(() => {})
+      .finally(() => setTimeout(check, 1000));
+  };
+
+  check();
+})();
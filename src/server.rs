@@ -0,0 +1,144 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub const LIVE_RELOAD_SCRIPT: &str = include_str!("script/live-reload.js");
+
+// The latest rendering of the served spec.
+#[derive(Debug, Default)]
+pub struct Preview {
+    html: String,
+    version: u64,
+}
+
+impl Preview {
+    pub fn update(&mut self, html: String) {
+        self.html = html;
+        self.version += 1;
+    }
+}
+
+// Serve the preview to every connection on the listener, one thread per
+// connection.
+pub fn serve(listener: TcpListener, preview: Arc<Mutex<Preview>>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let preview = Arc::clone(&preview);
+
+        thread::spawn(move || {
+            // The client may go away at any time, which isn't worth reporting.
+            let _ = handle_connection(stream, &preview);
+        });
+    }
+}
+
+fn handle_connection(stream: TcpStream, preview: &Mutex<Preview>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    // Skip the headers, there is nothing in them we care about.
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut pieces = request_line.split_whitespace();
+    let method = pieces.next().unwrap_or("");
+    let path = pieces.next().unwrap_or("");
+
+    let (status, content_type, body) = if method != "GET" {
+        (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed.".to_owned(),
+        )
+    } else {
+        let preview = preview.lock().unwrap();
+        match path {
+            "/" | "/index.html" if preview.version == 0 => (
+                "503 Service Unavailable",
+                "text/html; charset=utf-8",
+                format!(
+                    concat!(
+                        "<!doctype html><title>Not ready</title>",
+                        "<p>The spec couldn't be processed yet. See the terminal for errors.",
+                        "<script>{}</script>"
+                    ),
+                    LIVE_RELOAD_SCRIPT
+                ),
+            ),
+            "/" | "/index.html" => ("200 OK", "text/html; charset=utf-8", preview.html.clone()),
+            "/__version" => ("200 OK", "text/plain", preview.version.to_string()),
+            _ => ("404 Not Found", "text/plain", "Not found.".to_owned()),
+        }
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        concat!(
+            "HTTP/1.1 {}\r\n",
+            "Content-Type: {}\r\n",
+            "Content-Length: {}\r\n",
+            "Cache-Control: no-store\r\n",
+            "Connection: close\r\n",
+            "\r\n"
+        ),
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serve, Preview};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    fn get(port: u16, path: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let preview = Arc::new(Mutex::new(Preview::default()));
+
+        {
+            let preview = Arc::clone(&preview);
+            thread::spawn(move || serve(listener, preview));
+        }
+
+        assert!(get(port, "/").starts_with("HTTP/1.1 503"));
+
+        preview.lock().unwrap().update("<p>first".to_owned());
+        let response = get(port, "/");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("\r\n\r\n<p>first"));
+        assert!(get(port, "/__version").ends_with("\r\n\r\n1"));
+
+        preview.lock().unwrap().update("<p>second".to_owned());
+        assert!(get(port, "/").ends_with("\r\n\r\n<p>second"));
+        assert!(get(port, "/__version").ends_with("\r\n\r\n2"));
+
+        assert!(get(port, "/missing").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -37,6 +37,7 @@ fn transform_text_node(text_el: &NodeRef, markup_shorthands: &BoolSet<String>) -
     let mut text_els = vec![text_el.clone()];
 
     if markup_shorthands.get("biblio") {
+        text_els = process_text_nodes(&text_els, &SECTION_LINK_REG, section_link_replacer);
         text_els = process_text_nodes(&text_els, &BIBLIO_LINK_REG, biblio_link_replacer);
     }
 
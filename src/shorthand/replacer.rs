@@ -1,4 +1,4 @@
-#![allow(clippy::trivial_regex)]
+#![allow(clippy::trivial_regex, clippy::useless_concat)]
 
 use kuchiki::NodeRef;
 use regex::{Captures, Regex};
@@ -179,3 +179,34 @@ pub fn biblio_link_replacer(caps: &Captures) -> Vec<NodeRef> {
 
     vec![html::new_a(attrs, link_text)]
 }
+
+lazy_static! {
+    // regex for section link
+    pub static ref SECTION_LINK_REG: Regex = Regex::new(
+        r"(?x)
+        (?P<escape>\\)?
+        \[\[
+        (?P<section>\#[\w-]+)
+        (\|(?P<link_text>[^\]]+))?
+        \]\]"
+    )
+    .unwrap();
+}
+
+pub fn section_link_replacer(caps: &Captures) -> Vec<NodeRef> {
+    if caps.name("escape").is_some() {
+        return vec![html::new_text(&caps[0][1..])];
+    }
+
+    let link_text = match caps.name("link_text") {
+        Some(m) => m.as_str(),
+        None => "",
+    };
+
+    let attrs = btreemap! {
+        "data-section" => "",
+        "href" => &caps["section"],
+    };
+
+    vec![html::new_a(attrs, link_text)]
+}
@@ -1,12 +1,15 @@
 use kuchiki::traits::*;
 use kuchiki::NodeRef;
-use std::collections::{BTreeMap, HashMap};
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
 use crate::boilerplate::{self, retrieve_boilerplate_with_info};
 use crate::clean;
 use crate::config::SOURCE_FILE_EXTENSIONS;
 use crate::datablock;
+use crate::datadir::DataDirs;
 use crate::fix::{self, CodeSpanManager};
 use crate::heading;
 use crate::html;
@@ -14,11 +17,14 @@ use crate::line::Line;
 use crate::link::biblio::manager::BiblioManager;
 use crate::link::reference::manager::ReferenceManager;
 use crate::link::reference::Reference;
-use crate::link::{self, biblio::Biblio, dfn};
+use crate::link::{self, biblio::Biblio, dfn, SpecDataCache};
 use crate::markdown;
-use crate::metadata::{self, Metadata};
+use crate::metadata::{self, Metadata, MetadataSource};
 use crate::shorthand;
-use crate::util::reader;
+use crate::util::message::Diagnostics;
+
+// shared metadata that applies to all specs in its directory
+const METADATA_FILE_NAME: &str = "metadata.json";
 
 #[derive(Debug, Default)]
 pub struct Spec<'a> {
@@ -26,7 +32,9 @@ pub struct Spec<'a> {
     lines: Vec<Line>,
     pub md: Metadata,
     pub md_cli: Metadata,
-    pub macros: HashMap<&'a str, String>,
+    // metadata file given on the command line
+    pub md_file: Option<PathBuf>,
+    pub macros: HashMap<String, String>,
     pub html: String,
     dom: Option<NodeRef>,
     head: Option<NodeRef>,
@@ -42,11 +50,47 @@ pub struct Spec<'a> {
     pub normative_biblios: HashMap<String, Biblio>,
     // text => informative biblios
     pub informative_biblios: HashMap<String, Biblio>,
+    pub data_dirs: DataDirs,
+    pub spec_data_cache: SpecDataCache,
+    // boilerplate files used by this spec
+    pub boilerplate_files: BTreeSet<PathBuf>,
+    // metadata files used by this spec
+    pub metadata_files: BTreeSet<PathBuf>,
+    pub diagnostics: Diagnostics,
 }
 
 impl<'a> Spec<'a> {
-    pub fn new(infile: &str, md_cli: Metadata) -> Spec {
-        let lines = Spec::read_lines_from_source(infile);
+    // Load a spec from a file, or from stdin if the file is "-".
+    pub fn new(infile: &str, md_cli: Metadata) -> Spec<'_> {
+        let source = if infile == "-" {
+            let mut source = String::new();
+            io::stdin().read_to_string(&mut source).map(|_| source)
+        } else {
+            fs::read_to_string(infile)
+        };
+
+        match source {
+            Ok(source) => Spec::from_source(infile, &source, md_cli),
+            Err(err) => {
+                let mut spec = Spec::from_source(infile, "", md_cli);
+                spec.diagnostics
+                    .fatal(format!("Unable to read \"{}\": {}.", infile, err), None);
+                spec
+            }
+        }
+    }
+
+    // Build a spec from in-memory source text. The file name is only used to
+    // name the output file and in diagnostics.
+    pub fn from_source(infile: &'a str, source: &str, md_cli: Metadata) -> Spec<'a> {
+        let lines = source
+            .lines()
+            .enumerate()
+            .map(|(index, text)| Line {
+                index: 1 + (index as u32),
+                text: text.to_owned(),
+            })
+            .collect();
 
         let extra_styles = btreemap! {
             "md-lists" => include_str!("style/md-lists.css"),
@@ -62,54 +106,112 @@ impl<'a> Spec<'a> {
             extra_styles,
             reference_manager: ReferenceManager::new(),
             biblio_manager: BiblioManager::new(),
+            diagnostics: Diagnostics::new(infile),
+            data_dirs: DataDirs::resolve(infile, None, None),
             ..Default::default()
         }
     }
 
-    fn read_lines_from_source(infile: &str) -> Vec<Line> {
-        let mut lines: Vec<Line> = Vec::new();
-        if let Ok(src_lines) = reader::read_lines(infile) {
-            for (index, src_line) in src_lines.enumerate() {
-                if let Ok(text) = src_line {
-                    lines.push(Line {
-                        index: 1 + (index as u32),
-                        text,
-                    });
-                }
-            }
+    pub fn preprocess(&mut self) {
+        if self.diagnostics.has_fatal() {
+            return;
         }
-        lines
-    }
 
-    pub fn preprocess(&mut self) {
         self.assemble_document();
+
+        // Fatal errors leave nothing sensible to process.
+        if self.diagnostics.has_fatal() {
+            return;
+        }
+
         self.process_document();
     }
 
+    // Load a JSON metadata file.
+    fn load_metadata_file(&mut self, path: &Path) -> Option<Metadata> {
+        match fs::read_to_string(path) {
+            Ok(data) => {
+                self.metadata_files.insert(path.to_path_buf());
+                let source = MetadataSource::File(path.display().to_string());
+                Some(Metadata::from_json(data, source, &mut self.diagnostics))
+            }
+            Err(err) => {
+                self.diagnostics.error(
+                    format!("Unable to read \"{}\": {}.", path.display(), err),
+                    None,
+                );
+                None
+            }
+        }
+    }
+
     fn assemble_document(&mut self) {
         let lines = markdown::comment::remove_comments(&self.lines);
         self.lines = datablock::transform_data_blocks(self, &lines);
 
-        let (md_doc, lines) = metadata::parse_metadata(&self.lines);
+        let (md_doc, lines) = metadata::parse_metadata(&self.lines, &mut self.diagnostics);
         self.lines = lines;
 
-        // [default metadata] < [document metadata] < [command-line metadata]
+        // shared metadata next to the spec
+        let md_shared = if self.infile == "-" {
+            None
+        } else {
+            let path = Path::new(self.infile).with_file_name(METADATA_FILE_NAME);
+            if path.is_file() {
+                self.load_metadata_file(&path)
+            } else {
+                None
+            }
+        }
+        .unwrap_or_default();
+        let md_file = match self.md_file.clone() {
+            Some(path) => self.load_metadata_file(&path),
+            None => None,
+        }
+        .unwrap_or_default();
+
+        // [default metadata] < [metadata.json] < [document metadata]
+        // < [--md-file] < [command-line metadata]
         let mut md = {
-            let group = metadata::extract_group(&[&md_doc, &self.md_cli]);
-            let status = metadata::extract_status(&[&md_doc, &self.md_cli]);
-            let data =
-                retrieve_boilerplate_with_info("defaults", group.as_deref(), status.as_deref());
-            Metadata::from_json(data)
+            let layers = [&md_shared, &md_doc, &md_file, &self.md_cli];
+            let group = metadata::extract_group(&layers);
+            let status = metadata::extract_status(&layers);
+            match retrieve_boilerplate_with_info(
+                &self.data_dirs,
+                "defaults",
+                group.as_deref(),
+                status.as_deref(),
+            ) {
+                Ok((data, path)) => {
+                    self.boilerplate_files.extend(path);
+                    Metadata::from_json(data, MetadataSource::Default, &mut self.diagnostics)
+                }
+                Err(message) => {
+                    self.diagnostics.error(message, None);
+                    Metadata::new()
+                }
+            }
         };
+        md.join(md_shared);
         md.join(md_doc);
+        md.join(md_file);
         md.join(self.md_cli.clone());
 
-        md.compute_implicit_metadata();
+        md.compute_implicit_metadata(&mut self.diagnostics);
         md.fill_macros(self);
-        md.validate();
+        md.validate(&mut self.diagnostics);
         self.md = md;
+        self.fill_abstract_macro();
+
+        if self.diagnostics.has_fatal() {
+            return;
+        }
 
         self.reference_manager.set_data(&self.md);
+        self.reference_manager
+            .set_spec_data(&self.data_dirs.spec_data, &self.spec_data_cache);
+        self.biblio_manager
+            .set_spec_data(&self.data_dirs.spec_data, &self.spec_data_cache);
 
         let lines = markdown::parse(
             &self
@@ -118,11 +220,13 @@ impl<'a> Spec<'a> {
                 .map(|l| l.text.clone())
                 .collect::<Vec<String>>(),
             self.md.indent(),
+            &mut self.diagnostics,
         );
 
         self.html = lines.join("\n");
         boilerplate::add_header_footer(self);
-        self.html = self.fix_text(&self.html);
+        let html = std::mem::take(&mut self.html);
+        self.html = self.fix_text(&html);
 
         let dom = kuchiki::parse_html().one(self.html.clone());
         self.head = html::select_first(&dom, "head");
@@ -139,6 +243,8 @@ impl<'a> Spec<'a> {
         boilerplate::fill_spec_metadata_section(self);
         boilerplate::fill_copyright_section(self);
         boilerplate::fill_abstract_section(self);
+        boilerplate::add_warning(self);
+        boilerplate::add_expiry_notice(self);
         shorthand::transform_shortcuts(self);
         fix::canonicalize_shortcuts(self);
 
@@ -149,6 +255,7 @@ impl<'a> Spec<'a> {
         boilerplate::add_index_section(self);
         boilerplate::add_references_section(self);
         heading::process_headings(self);
+        link::process_section_links(self);
         boilerplate::fill_toc_section(self);
         link::add_self_links(self);
 
@@ -159,26 +266,67 @@ impl<'a> Spec<'a> {
         clean::clean_dom(self);
     }
 
+    // Serialize the processed document, if there is one.
+    pub fn render(&self) -> Option<String> {
+        self.dom.as_ref().map(NodeRef::to_string)
+    }
+
+    // Write the processed document to a file, or to stdout if the file is "-".
     pub fn finish(&mut self, outfile: Option<&str>) {
+        // The document couldn't be assembled, so there is nothing to write.
+        if self.dom.is_none() {
+            return;
+        }
+
         let outfile = self.handle_outfile(outfile);
-        let rendered = self.dom().to_string();
-        fs::write(outfile, rendered).expect("unable to write file");
+
+        let result = if outfile == "-" {
+            self.write_to(&mut io::stdout().lock())
+        } else {
+            File::create(&outfile).and_then(|file| self.write_to(&mut BufWriter::new(file)))
+        };
+
+        if let Err(err) = result {
+            self.diagnostics.fatal(
+                format!("Unable to write to \"{}\": {}.", outfile, err),
+                None,
+            );
+        }
+    }
+
+    // Serialize the processed document into the sink.
+    pub fn write_to<W: Write>(&self, sink: &mut W) -> io::Result<()> {
+        if let Some(ref dom) = self.dom {
+            dom.serialize(sink)?;
+        }
+        sink.flush()
     }
 
     // Do several textual replacements with this spec.
-    pub fn fix_text(&self, text: &str) -> String {
+    pub fn fix_text(&mut self, text: &str) -> String {
         if self.md.markup_shorthands.get("markdown") {
             let mut code_span_manager = CodeSpanManager::new(text.to_owned());
-            code_span_manager.map_text_pieces(|text: &str| fix::replace_macros(text, &self.macros));
+            code_span_manager.map_text_pieces(|text: &str| {
+                fix::replace_macros(text, &self.macros, &mut self.diagnostics)
+            });
             code_span_manager.map_text_pieces(fix::fix_typography);
             code_span_manager.extract()
         } else {
-            let mut text = fix::replace_macros(text, &self.macros);
+            let mut text = fix::replace_macros(text, &self.macros, &mut self.diagnostics);
             text = fix::fix_typography(&text);
             text
         }
     }
 
+    // The abstract is processed like the body text, so that paragraphs,
+    // macros and typography work in it too.
+    fn fill_abstract_macro(&mut self) {
+        let abs = markdown::parse(&self.md.abs, self.md.indent(), &mut self.diagnostics).join("\n");
+        let abs = self.fix_text(&abs);
+        self.macros.insert("abstract".to_owned(), abs);
+    }
+
+    #[allow(clippy::needless_borrow)]
     fn handle_outfile(&self, outfile: Option<&str>) -> String {
         if let Some(outfile) = outfile {
             outfile.to_owned()
@@ -193,6 +341,30 @@ impl<'a> Spec<'a> {
         }
     }
 
+    // All files the output depends on: the source file and the boilerplate and
+    // spec data files read while processing it.
+    pub fn dependencies(&self) -> BTreeSet<PathBuf> {
+        let mut dependencies = BTreeSet::new();
+        dependencies.insert(PathBuf::from(self.infile));
+        dependencies.extend(self.boilerplate_files.iter().cloned());
+        dependencies.extend(self.metadata_files.iter().cloned());
+        dependencies.extend(
+            self.reference_manager
+                .external_reference_source
+                .loaded_files()
+                .iter()
+                .cloned(),
+        );
+        dependencies.extend(
+            self.biblio_manager
+                .biblio_source
+                .loaded_files()
+                .iter()
+                .cloned(),
+        );
+        dependencies
+    }
+
     pub fn dom(&self) -> &NodeRef {
         self.dom.as_ref().unwrap()
     }
@@ -205,3 +377,46 @@ impl<'a> Spec<'a> {
         self.body.as_ref().unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::Spec;
+    use crate::html;
+    use crate::metadata::Metadata;
+
+    #[test]
+    fn test_abstract_links() {
+        let base_dir = env::temp_dir().join(format!("bikeshed-rs-abstract-{}", process::id()));
+        fs::create_dir_all(&base_dir).unwrap();
+
+        let infile = base_dir.join("abstract.bs").to_string_lossy().into_owned();
+        let source = fs::read_to_string("tests/basic001.bs").unwrap().replace(
+            "Abstract: A document without any content.",
+            "Abstract: A <a>light source</a> as defined in [[!RFC2119]].",
+        );
+        let biblio = concat!(
+            "<pre class=biblio>\n",
+            "{\"RFC2119\": {\"title\": \"Key words\", \"href\": \"https://example.test/\"}}\n",
+            "</pre>\n",
+        );
+        fs::write(&infile, format!("{}\n{}", source, biblio)).unwrap();
+
+        let mut spec = Spec::new(&infile, Metadata::new());
+        spec.preprocess();
+
+        let abstract_el = html::select_first(spec.dom(), "[data-fill-with='abstract']").unwrap();
+        let hrefs = html::select(&abstract_el, "a")
+            .filter_map(|a_el| html::get_attr(&a_el, "href"))
+            .collect::<Vec<String>>();
+        assert_eq!(hrefs.len(), 2);
+        assert!(hrefs[0].ends_with("#light-source"));
+        assert_eq!(hrefs[1], "#biblio-rfc2119");
+        assert!(spec.normative_biblios.contains_key("RFC2119"));
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
@@ -68,6 +68,7 @@ struct NodePair {
 }
 
 // Compare DOM trees recursively.
+#[allow(clippy::question_mark)]
 fn is_equal(lhs: &NodeRef, rhs: &NodeRef) -> Result<(), CompareError> {
     if lhs.data() != rhs.data() {
         return Err(CompareError::Data(NodePair {
@@ -131,6 +132,19 @@ fn test_spec() {
         "metadata014",
         "metadata015",
         "metadata016",
+        "metadata-expires001",
+        "metadata-expires002",
+        "metadata-expires003",
+        "metadata-expires004",
+        "metadata-expires005",
+        "metadata-expires006",
+        "metadata-order001",
+        "metadata-repository001",
+        "abstract001",
+        "biblio001",
+        "index003",
+        // macros
+        "macros001",
         // markdown
         "markdown001",
         "markdown002",
@@ -151,6 +165,9 @@ fn test_spec() {
         "links003",
         "links004",
         "links006",
+        "section-links001",
+        // elementdef
+        "elementdef001",
         // var
         "var001",
     ];
@@ -162,29 +179,46 @@ fn test_spec() {
         let mut spec = Spec::new(src_path.to_str().unwrap(), Metadata::new());
         spec.preprocess();
 
-        match fs::read_to_string(target_path.to_str().unwrap()) {
-            Ok(html) => {
-                let expect_dom = kuchiki::parse_html().one(html);
-
-                if let Err(err) = is_equal(spec.dom(), &expect_dom) {
-                    match err {
-                        CompareError::Data(pair) => eprintln!(
-                            "[{}] [Wrong Data]\nExpect:\n{}\n\nFound:\n{}",
-                            name,
-                            pair.expect.to_string(),
-                            pair.result.to_string()
-                        ),
-                        CompareError::Number(pair) => eprintln!(
-                            "[{}] [Wrong Children Number]\nExpect:\n{}\n\nFound:\n{}",
-                            name,
-                            pair.expect.to_string(),
-                            pair.result.to_string()
-                        ),
-                    }
-                    panic!();
-                }
+        let html = fs::read_to_string(&target_path)
+            .unwrap_or_else(|err| panic!("Fail to read {}: {}", target_path.display(), err));
+        let expect_dom = kuchiki::parse_html().one(html);
+
+        if let Err(err) = is_equal(spec.dom(), &expect_dom) {
+            match err {
+                CompareError::Data(pair) => eprintln!(
+                    "[{}] [Wrong Data]\nExpect:\n{}\n\nFound:\n{}",
+                    name,
+                    pair.expect.to_string(),
+                    pair.result.to_string()
+                ),
+                CompareError::Number(pair) => eprintln!(
+                    "[{}] [Wrong Children Number]\nExpect:\n{}\n\nFound:\n{}",
+                    name,
+                    pair.expect.to_string(),
+                    pair.result.to_string()
+                ),
             }
-            _ => die!("Fail to read expect file"),
+            panic!();
         }
     }
 }
+
+#[test]
+fn test_spec_from_source() {
+    let source = fs::read_to_string("tests/basic001.bs").unwrap();
+    let expect = fs::read_to_string("tests/basic001.html").unwrap();
+
+    let mut spec = Spec::from_source("basic001.bs", &source, Metadata::new());
+    spec.preprocess();
+
+    assert_eq!(spec.diagnostics.worst(), None);
+    let rendered = spec.render().unwrap();
+
+    let mut output = Vec::new();
+    spec.write_to(&mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), rendered);
+
+    let result_dom = kuchiki::parse_html().one(rendered);
+    let expect_dom = kuchiki::parse_html().one(expect);
+    assert!(is_equal(&result_dom, &expect_dom).is_ok());
+}
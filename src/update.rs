@@ -0,0 +1,533 @@
+use flate2::read::GzDecoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::config;
+
+// kinds of spec data, each one is a directory of "<kind>-<group>.data" files
+const DATA_KINDS: [&str; 2] = ["anchors", "biblio"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct UpdateCounts {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+// An entry of a data file.
+#[derive(Debug)]
+struct Entry {
+    // the key that decides the group of the entry
+    key: String,
+    // what identifies the entry across updates
+    id: String,
+    // the lines of the entry
+    text: String,
+}
+
+// Walk through the lines of a data file, keeping track of the line number.
+struct Cursor<'a> {
+    name: &'a str,
+    lines: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(name: &'a str, data: &'a str) -> Self {
+        Cursor {
+            name,
+            lines: data.lines().collect(),
+            pos: 0,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.pos >= self.lines.len()
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        match self.lines.get(self.pos) {
+            Some(line) => {
+                self.pos += 1;
+                Ok(line)
+            }
+            None => Err(self.error("Unexpected end of file.")),
+        }
+    }
+
+    // Read lines up to the "-" line that ends an entry.
+    fn until_end(&mut self) -> Result<Vec<&'a str>, String> {
+        let mut lines = Vec::new();
+
+        loop {
+            match self.next()? {
+                "-" => return Ok(lines),
+                line => lines.push(line),
+            }
+        }
+    }
+
+    // Report an error on the last line read.
+    fn error(&self, message: &str) -> String {
+        format!("{}:{}: {}", self.name, self.pos.max(1), message)
+    }
+
+    fn text_since(&self, start: usize) -> String {
+        let mut text = self.lines[start..self.pos].join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+fn parse_anchors(name: &str, data: &str) -> Result<Vec<Entry>, String> {
+    let mut cursor = Cursor::new(name, data);
+    let mut entries = Vec::new();
+
+    while !cursor.is_done() {
+        let start = cursor.pos;
+
+        let key = cursor.next()?;
+        if key.is_empty() {
+            return Err(cursor.error("Missing anchor text."));
+        }
+        let link_type = cursor.next()?;
+        if link_type.is_empty() {
+            return Err(cursor.error("Missing anchor type."));
+        }
+        cursor.next()?; // spec
+        cursor.next()?; // shortname
+        cursor.next()?; // level
+        let status = cursor.next()?;
+        if status != "current" && status != "snapshot" {
+            return Err(cursor.error(&format!(
+                "Anchor status must be \"current\" or \"snapshot\". Got: \"{}\".",
+                status
+            )));
+        }
+        let url = cursor.next()?;
+        if url.is_empty() {
+            return Err(cursor.error("Missing anchor url."));
+        }
+        cursor.next()?; // export
+        cursor.next()?; // normative
+        let link_fors = cursor.until_end()?;
+
+        // Anchors of the same url can still differ in their type or for values.
+        entries.push(Entry {
+            key: key.to_owned(),
+            id: format!("{}\n{}\n{}\n{}", key, link_type, url, link_fors.join("\n")),
+            text: cursor.text_since(start),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn parse_biblio(name: &str, data: &str) -> Result<Vec<Entry>, String> {
+    let mut cursor = Cursor::new(name, data);
+    let mut entries = Vec::new();
+
+    while !cursor.is_done() {
+        let start = cursor.pos;
+
+        let full_key = cursor.next()?;
+        let (prefix, key) = match (full_key.get(0..1), full_key.get(2..)) {
+            (Some(prefix), Some(key)) if full_key[1..].starts_with(':') && !key.is_empty() => {
+                (prefix, key.trim_end())
+            }
+            _ => {
+                return Err(cursor.error(&format!("Invalid biblio key: \"{}\".", full_key)));
+            }
+        };
+
+        match prefix {
+            "d" => {
+                // link text, date, status, title, url, current url, obsoleted by,
+                // other, et al, then the authors
+                for _ in 0..9 {
+                    cursor.next()?;
+                }
+                cursor.until_end()?;
+            }
+            "s" | "a" => {
+                // link text, then the data or the aliased key
+                cursor.next()?;
+                cursor.next()?;
+                if cursor.next()? != "-" {
+                    return Err(cursor.error("Expected \"-\" at the end of the entry."));
+                }
+            }
+            _ => {
+                return Err(cursor.error(&format!("Unknown biblio prefix: \"{}\".", prefix)));
+            }
+        }
+
+        entries.push(Entry {
+            key: key.to_owned(),
+            id: key.to_owned(),
+            text: cursor.text_since(start),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn parse_entries(kind: &str, name: &str, data: &str) -> Result<Vec<Entry>, String> {
+    match kind {
+        "anchors" => parse_anchors(name, data),
+        _ => parse_biblio(name, data),
+    }
+}
+
+// The kind of data in the file, if it is a data file: "<kind>/<kind>-*.data".
+fn data_kind(path: &str) -> Option<&'static str> {
+    let mut pieces = path.rsplit('/');
+    let file_name = pieces.next()?;
+    let dir_name = pieces.next()?;
+
+    DATA_KINDS.iter().cloned().find(|kind| {
+        dir_name == *kind
+            && file_name.starts_with(&format!("{}-", kind))
+            && file_name.ends_with(".data")
+    })
+}
+
+fn collect_dir_files(base_dir: &Path, dir: &Path, files: &mut BTreeMap<String, String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            collect_dir_files(base_dir, &path, files);
+            continue;
+        }
+
+        let rel_path = path
+            .strip_prefix(base_dir)
+            .unwrap()
+            .iter()
+            .map(|piece| piece.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if data_kind(&rel_path).is_some() {
+            if let Ok(data) = fs::read_to_string(&path) {
+                files.insert(rel_path, data);
+            }
+        }
+    }
+}
+
+fn collect_tarball_files(from: &Path) -> Result<BTreeMap<String, String>, String> {
+    let fail = |err: &dyn ToString| {
+        format!(
+            "Fail to read tarball \"{}\": {}.",
+            from.display(),
+            err.to_string()
+        )
+    };
+
+    let bytes = fs::read(from).map_err(|err| fail(&err))?;
+    let reader: Box<dyn Read> = if bytes.starts_with(&[0x1f, 0x8b]) {
+        Box::new(GzDecoder::new(&bytes[..]))
+    } else {
+        Box::new(&bytes[..])
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    let mut files = BTreeMap::new();
+
+    for entry in archive.entries().map_err(|err| fail(&err))? {
+        let mut entry = entry.map_err(|err| fail(&err))?;
+        let path = entry
+            .path()
+            .map_err(|err| fail(&err))?
+            .iter()
+            .map(|piece| piece.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        if data_kind(&path).is_some() {
+            let mut data = String::new();
+            entry.read_to_string(&mut data).map_err(|err| fail(&err))?;
+            files.insert(path, data);
+        }
+    }
+
+    Ok(files)
+}
+
+// Parse the data files of a kind, in the order of their paths.
+fn parse_files(kind: &str, files: &BTreeMap<String, String>) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+
+    for (path, data) in files.iter() {
+        if data_kind(path) == Some(kind) {
+            entries.extend(parse_entries(kind, path, data)?);
+        }
+    }
+
+    Ok(entries)
+}
+
+// Regenerate the grouped data files in the spec data directory from a
+// directory or a (gzipped) tarball in the Bikeshed data layout. Only the kinds
+// of data found in the source are touched, and nothing is written unless all
+// the source files are valid.
+pub fn update_spec_data(
+    from: &Path,
+    spec_data_dir: &Path,
+) -> Result<BTreeMap<&'static str, UpdateCounts>, String> {
+    let files = if from.is_dir() {
+        let mut files = BTreeMap::new();
+        collect_dir_files(from, from, &mut files);
+        files
+    } else {
+        collect_tarball_files(from)?
+    };
+
+    let mut new_entries = BTreeMap::new();
+
+    for kind in DATA_KINDS.iter() {
+        if files.keys().any(|path| data_kind(path) == Some(kind)) {
+            new_entries.insert(*kind, parse_files(kind, &files)?);
+        }
+    }
+
+    if new_entries.is_empty() {
+        return Err(format!(
+            "No anchors or biblio data files found in \"{}\".",
+            from.display()
+        ));
+    }
+
+    let mut report = BTreeMap::new();
+    // (path, data) of the files to write and the paths of the files they replace
+    let mut new_files = Vec::new();
+    let mut old_paths = Vec::new();
+
+    for (kind, entries) in new_entries.iter() {
+        let kind = *kind;
+        let kind_dir = spec_data_dir.join(kind);
+
+        // Existing files that can't be parsed are simply replaced.
+        let mut old_files = BTreeMap::new();
+        collect_dir_files(spec_data_dir, &kind_dir, &mut old_files);
+        let old_entries = old_files
+            .iter()
+            .filter_map(|(path, data)| parse_entries(kind, path, data).ok())
+            .flatten()
+            .map(|entry| (entry.id, entry.text))
+            .collect::<HashMap<String, String>>();
+
+        // group => entries, keeping the first entry of each id
+        let mut groups: BTreeMap<String, Vec<&Entry>> = BTreeMap::new();
+        let mut new_texts: HashMap<&str, &str> = HashMap::new();
+
+        for entry in entries.iter() {
+            if new_texts.contains_key(entry.id.as_str()) {
+                continue;
+            }
+            new_texts.insert(&entry.id, &entry.text);
+            groups
+                .entry(config::generate_group_name(&entry.key))
+                .or_default()
+                .push(entry);
+        }
+
+        let mut counts = UpdateCounts::default();
+        for (id, text) in new_texts.iter() {
+            match old_entries.get(*id) {
+                Some(old_text) if old_text != text => counts.changed += 1,
+                Some(_) => {}
+                None => counts.added += 1,
+            }
+        }
+        counts.removed = old_entries
+            .keys()
+            .filter(|id| !new_texts.contains_key(id.as_str()))
+            .count();
+
+        for (group, entries) in groups {
+            let data = entries
+                .iter()
+                .map(|entry| entry.text.as_str())
+                .collect::<String>();
+            new_files.push((kind_dir.join(format!("{}-{}.data", kind, group)), data));
+        }
+        old_paths.extend(old_files.keys().map(|path| spec_data_dir.join(path)));
+
+        report.insert(kind, counts);
+    }
+
+    write_files(&new_files, &old_paths)?;
+
+    Ok(report)
+}
+
+// Replace the old data files with the new ones. The new files are written
+// next to their final paths first, so that the old data is left as it is if
+// any of them can't be written.
+fn write_files(new_files: &[(PathBuf, String)], old_paths: &[PathBuf]) -> Result<(), String> {
+    let temp_path = |path: &Path| path.with_extension("data.tmp");
+    let write_error = |path: &Path, err: io::Error| {
+        format!(
+            "Fail to write spec data to \"{}\": {}.",
+            path.display(),
+            err
+        )
+    };
+
+    for (i, (path, data)) in new_files.iter().enumerate() {
+        let result = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| fs::write(temp_path(path), data));
+
+        if let Err(err) = result {
+            for (path, _) in new_files[..i].iter() {
+                let _ = fs::remove_file(temp_path(path));
+            }
+            return Err(write_error(path, err));
+        }
+    }
+
+    for (path, _) in new_files.iter() {
+        fs::rename(temp_path(path), path).map_err(|err| write_error(path, err))?;
+    }
+
+    for path in old_paths {
+        if !new_files.iter().any(|(new_path, _)| new_path == path) {
+            fs::remove_file(path).map_err(|err| write_error(path, err))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{data_kind, parse_anchors, parse_biblio, update_spec_data, UpdateCounts};
+    use std::env;
+    use std::fs;
+
+    const ANCHOR: &str = concat!(
+        "\"foo\"\nenum-value\nbar-1\nbar\n1\ncurrent\n",
+        "https://example.org/#foo\n1\n1\nBar\n-\n"
+    );
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_anchors("a", ANCHOR).unwrap().len(), 1);
+        assert_eq!(
+            parse_anchors("a", &ANCHOR.replace("current", "old")).unwrap_err(),
+            "a:6: Anchor status must be \"current\" or \"snapshot\". Got: \"old\"."
+        );
+        assert_eq!(
+            parse_anchors("a", &ANCHOR[..ANCHOR.len() - 2]).unwrap_err(),
+            "a:10: Unexpected end of file."
+        );
+
+        let biblio = "a:foo\nFOO\nbar\n-\ns:baz\nBAZ\n<b>baz</b>\n-\n";
+        let entries = parse_biblio("b", biblio).unwrap();
+        assert_eq!(entries[1].key, "baz");
+        assert_eq!(
+            parse_biblio("b", "x:foo\nFOO\nbar\n-\n").unwrap_err(),
+            "b:1: Unknown biblio prefix: \"x\"."
+        );
+    }
+
+    #[test]
+    fn test_data_kind() {
+        assert_eq!(
+            data_kind("readonly/anchors/anchors-fo.data"),
+            Some("anchors")
+        );
+        assert_eq!(data_kind("biblio/biblio-fo.data"), Some("biblio"));
+        assert_eq!(data_kind("anchors/biblio-fo.data"), None);
+        assert_eq!(data_kind("anchors-fo.data"), None);
+    }
+
+    #[test]
+    fn test_update_spec_data() {
+        let base_dir = env::temp_dir().join(format!("bikeshed-rs-update-{}", std::process::id()));
+        let from = base_dir.join("from");
+        let to = base_dir.join("to");
+        fs::create_dir_all(from.join("anchors")).unwrap();
+
+        let other = ANCHOR.replace("foo", "quux");
+        fs::write(
+            from.join("anchors").join("anchors-xx.data"),
+            format!("{}{}", ANCHOR, other),
+        )
+        .unwrap();
+        let report = update_spec_data(&from, &to).unwrap();
+        assert_eq!(
+            report["anchors"],
+            UpdateCounts {
+                added: 2,
+                removed: 0,
+                changed: 0,
+            }
+        );
+        assert_eq!(
+            fs::read_to_string(to.join("anchors").join("anchors-fo.data")).unwrap(),
+            ANCHOR
+        );
+        assert!(to.join("anchors").join("anchors-qu.data").is_file());
+
+        fs::write(
+            from.join("anchors").join("anchors-xx.data"),
+            ANCHOR.replace("current", "snapshot"),
+        )
+        .unwrap();
+        let report = update_spec_data(&from, &to).unwrap();
+        assert_eq!(
+            report["anchors"],
+            UpdateCounts {
+                added: 0,
+                removed: 1,
+                changed: 1,
+            }
+        );
+        assert!(!to.join("anchors").join("anchors-qu.data").exists());
+        assert!(!report.contains_key("biblio"));
+
+        // Anchors with the same url but another type or for value are kept apart.
+        let snapshot = ANCHOR.replace("current", "snapshot");
+        let other_type = snapshot.replace("enum-value", "dfn");
+        let other_for = snapshot.replace("Bar\n", "Baz\n");
+        fs::write(
+            from.join("anchors").join("anchors-xx.data"),
+            format!("{}{}{}", snapshot, other_type, other_for),
+        )
+        .unwrap();
+        let report = update_spec_data(&from, &to).unwrap();
+        assert_eq!(
+            report["anchors"],
+            UpdateCounts {
+                added: 2,
+                removed: 0,
+                changed: 0,
+            }
+        );
+
+        // The old data stays in place if the new data can't be written.
+        let data_path = to.join("anchors").join("anchors-fo.data");
+        let old_data = fs::read_to_string(&data_path).unwrap();
+        fs::create_dir(to.join("anchors").join("anchors-fo.data.tmp")).unwrap();
+        fs::write(from.join("anchors").join("anchors-xx.data"), ANCHOR).unwrap();
+        assert!(update_spec_data(&from, &to).is_err());
+        assert_eq!(fs::read_to_string(&data_path).unwrap(), old_data);
+
+        fs::remove_dir_all(&base_dir).unwrap();
+    }
+}
@@ -35,6 +35,7 @@ where
 
     // Returns the value for the key in bool set.
     // If no value is found for the key, then default value is returned.
+    #[allow(clippy::multiple_bound_locations)]
     pub fn get<Q: ?Sized>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
@@ -57,6 +58,7 @@ mod tests {
     use super::BoolSet;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_bool_set() {
         {
             let bs = BoolSet::<&str>::new_with_default(true);
@@ -1,11 +1,11 @@
 use chrono::format::strftime::StrftimeItems;
 use chrono::format::{DelayedFormat, ParseError};
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, Days, Months, NaiveDate};
 use std::fmt;
 
 pub type ParseResult = Result<Date, ParseError>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Date {
     pub date: NaiveDate,
 }
@@ -47,4 +47,12 @@ impl Date {
     pub fn day(&self) -> u32 {
         self.date.day()
     }
+
+    // Add a number of calendar months and then a number of days to this date.
+    pub fn checked_add(&self, months: u32, days: u64) -> Option<Date> {
+        self.date
+            .checked_add_months(Months::new(months))
+            .and_then(|date| date.checked_add_days(Days::new(days)))
+            .map(Date::new)
+    }
 }
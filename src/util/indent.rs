@@ -24,10 +24,10 @@ pub fn get_indent_level(text: &str, tab_size: u32) -> u32 {
     indent_level
 }
 
-pub fn trim_indent(text: &str, indent_level: u32, tab_size: u32) -> String {
+pub fn trim_indent(text: &str, indent_level: u32, tab_size: u32) -> Result<String, String> {
     // Allow empty line.
     if text.trim().is_empty() {
-        return text.to_owned();
+        return Ok(text.to_owned());
     }
 
     let tab_size = tab_size as usize;
@@ -41,9 +41,9 @@ pub fn trim_indent(text: &str, indent_level: u32, tab_size: u32) -> String {
         {
             offset += tab_size;
         } else {
-            die!("\"{}\" isn't indented enough.", text);
+            return Err(format!("\"{}\" isn't indented enough.", text));
         }
     }
 
-    text[offset..].to_owned()
+    Ok(text[offset..].to_owned())
 }
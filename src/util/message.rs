@@ -1,15 +1,180 @@
-#[macro_export]
-macro_rules! die {
-    ($($x:expr),+) => ({
-        eprintln!($($x),+);
-        std::process::exit(1);
-    });
-
-    ($($x:expr),+; $line:expr) => ({
-        if let Some(line) = $line {
-            eprint!("[Line {}] ",line);
-        }
-        eprintln!($($x),+);
-        std::process::exit(1);
-    });
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl Severity {
+    // The process exit code for a run whose worst diagnostic has this severity.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Severity::Warning => 0,
+            Severity::Error => 1,
+            Severity::Fatal => 2,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(fmt, "warning"),
+            Severity::Error => write!(fmt, "error"),
+            Severity::Fatal => write!(fmt, "fatal error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+impl Diagnostic {
+    pub fn new<T: Into<String>>(severity: Severity, message: T) -> Self {
+        Diagnostic {
+            severity,
+            message: message.into(),
+            file: None,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        // <file>:<line>:<column>: <severity>: <message>
+        if let Some(ref file) = self.file {
+            write!(fmt, "{}:", file)?;
+        }
+        if let Some(line) = self.line {
+            write!(fmt, "{}:", line)?;
+            if let Some(column) = self.column {
+                write!(fmt, "{}:", column)?;
+            }
+        }
+        if self.file.is_some() || self.line.is_some() {
+            write!(fmt, " ")?;
+        }
+        write!(fmt, "{}: {}", self.severity, self.message)
+    }
+}
+
+// All diagnostics reported while processing a spec.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    // Diagnostics that carry a line number refer to this file.
+    file: Option<String>,
+    items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(file: &str) -> Self {
+        Diagnostics {
+            file: Some(file.to_owned()),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, mut diagnostic: Diagnostic) {
+        if diagnostic.file.is_none() && diagnostic.line.is_some() {
+            diagnostic.file = self.file.clone();
+        }
+        self.items.push(diagnostic);
+    }
+
+    pub fn report<T: Into<String>>(&mut self, severity: Severity, message: T, line: Option<u32>) {
+        let mut diagnostic = Diagnostic::new(severity, message);
+        diagnostic.line = line;
+        self.push(diagnostic);
+    }
+
+    // Report a diagnostic that points at a column of the line, counted from 1.
+    pub fn report_at<T: Into<String>>(
+        &mut self,
+        severity: Severity,
+        message: T,
+        line: u32,
+        column: u32,
+    ) {
+        let mut diagnostic = Diagnostic::new(severity, message);
+        diagnostic.line = Some(line);
+        diagnostic.column = Some(column);
+        self.push(diagnostic);
+    }
+
+    pub fn warning<T: Into<String>>(&mut self, message: T, line: Option<u32>) {
+        self.report(Severity::Warning, message, line);
+    }
+
+    pub fn error<T: Into<String>>(&mut self, message: T, line: Option<u32>) {
+        self.report(Severity::Error, message, line);
+    }
+
+    pub fn fatal<T: Into<String>>(&mut self, message: T, line: Option<u32>) {
+        self.report(Severity::Fatal, message, line);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter()
+    }
+
+    // The highest severity among all diagnostics, if any.
+    pub fn worst(&self) -> Option<Severity> {
+        self.items
+            .iter()
+            .map(|diagnostic| diagnostic.severity)
+            .max()
+    }
+
+    pub fn has_fatal(&self) -> bool {
+        self.worst() == Some(Severity::Fatal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Diagnostics, Severity};
+
+    #[test]
+    fn test_diagnostics() {
+        let mut diagnostics = Diagnostics::new("foo.bs");
+        assert_eq!(diagnostics.worst(), None);
+
+        diagnostics.warning("Something odd.", None);
+        assert_eq!(diagnostics.worst(), Some(Severity::Warning));
+        assert!(!diagnostics.has_fatal());
+
+        diagnostics.error("Something wrong.", Some(3));
+        diagnostics.push(Diagnostic::new(Severity::Warning, "Something else."));
+        diagnostics.report_at(Severity::Error, "Something misplaced.", 4, 7);
+        assert_eq!(diagnostics.iter().count(), 4);
+        assert_eq!(diagnostics.worst(), Some(Severity::Error));
+
+        let rendered = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>();
+        assert_eq!(
+            rendered,
+            vec![
+                "warning: Something odd.",
+                "foo.bs:3: error: Something wrong.",
+                "warning: Something else.",
+                "foo.bs:4:7: error: Something misplaced.",
+            ]
+        );
+
+        diagnostics.fatal("Can't go on.", None);
+        assert!(diagnostics.has_fatal());
+        assert_eq!(diagnostics.worst().unwrap().exit_code(), 2);
+    }
 }
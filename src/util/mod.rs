@@ -1,8 +1,7 @@
-#[macro_use]
-pub mod message;
 pub mod boolish;
 pub mod boolset;
 pub mod date;
 pub mod indent;
+pub mod message;
 pub mod reader;
 pub mod regex;
@@ -1,8 +1,12 @@
-use std::fs::File;
 use std::io::{self, BufRead};
-use std::path::Path;
 
-pub fn read_lines<P: AsRef<Path>>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>> {
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+// Read the next line, treating the end of input as an error.
+pub fn next_line<B: BufRead>(lines: &mut io::Lines<B>) -> io::Result<String> {
+    match lines.next() {
+        Some(line) => line,
+        None => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "unexpected end of file",
+        )),
+    }
 }